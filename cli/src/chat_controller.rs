@@ -1,85 +1,417 @@
-use crate::dialog::{confirm_execute, input_chat_prompt};
-use anyhow::{bail, Context};
-use dialoguer::BasicHistory;
-use lib::ChatController;
-use std::env;
-use std::process::Command;
+use crate::code_actions::offer_code_actions;
+use crate::completion::ChatCompletion;
+use crate::dialog::{confirm, confirm_execute, confirm_typed, input_chat_prompt};
+use crate::diff::print_file_diff;
+use crate::history::PersistentHistory;
+use crate::markdown::render_markdown;
+use crate::storage::Storage;
+use anyhow::Context;
+use console::Style;
+use lib::{
+    command_failure_prompt, label_file_placeholders, placeholder_file_paths, propose_file_edits,
+    shell_command, strip_code_fences, ChatController, CommandAssessment, CommandPolicy, EditBackup,
+    RiskLevel,
+};
+use std::cell::RefCell;
+use std::io::{stdout, BufRead, BufReader, IsTerminal};
+use std::process::Stdio;
+
+const REVERT_COMMAND: &str = "/revert";
+const MULTILINE_HINT: &str =
+    "Wrap multi-line prompts in \"\"\", use /edit to open $EDITOR and Ctrl-R to search old prompts";
 
 pub(crate) struct ChatLoopController {
-    history: BasicHistory,
+    history: PersistentHistory,
+    completion: ChatCompletion,
+    render_markdown: bool,
+    interactive: bool,
+    policy: CommandPolicy,
+    shell: String,
 }
 
 impl ChatLoopController {
-    pub(crate) fn default() -> Self {
-        println!("Enter your prompt below. Leave it blank to cancel");
+    pub(crate) fn new(
+        completion: ChatCompletion,
+        history: PersistentHistory,
+        raw: bool,
+        policy: CommandPolicy,
+        shell: String,
+    ) -> Self {
+        println!("Enter your prompt below. Leave it blank to cancel, type /help for chat commands");
+        println!("{MULTILINE_HINT}");
         Self {
-            history: BasicHistory::new().max_entries(8).no_duplicates(true),
+            history,
+            completion,
+            render_markdown: !raw && stdout().is_terminal(),
+            interactive: stdout().is_terminal(),
+            policy,
+            shell,
         }
     }
 }
 
 impl ChatController for ChatLoopController {
     fn create_prompt(&mut self) -> anyhow::Result<Option<String>> {
-        input_chat_prompt(&mut self.history)
+        input_chat_prompt(&mut self.history, &self.completion)
     }
 
     fn on_completion(&self, completion: &str) -> anyhow::Result<()> {
-        println!("{completion}");
+        match self.render_markdown {
+            true => println!("{}", render_markdown(completion)),
+            false => println!("{completion}"),
+        }
+
+        if self.interactive {
+            offer_code_actions(completion, &self.policy, &self.shell)?;
+        }
+        Ok(())
+    }
+
+    fn on_command_output(&self, output: &str) -> anyhow::Result<()> {
+        println!("{output}");
         Ok(())
     }
 }
 
 pub(crate) struct ExecuteLoopController {
-    history: BasicHistory,
+    history: PersistentHistory,
+    completion: ChatCompletion,
     skip_confirmation: bool,
+    policy: CommandPolicy,
+    shell: String,
+    /// Failure report accepted by user, sent as the next prompt
+    failure_prompt: RefCell<Option<String>>,
 }
 
 impl ExecuteLoopController {
-    pub(crate) fn new(skip_confirmation: bool) -> Self {
+    pub(crate) fn new(
+        skip_confirmation: bool,
+        history: PersistentHistory,
+        policy: CommandPolicy,
+        shell: String,
+    ) -> Self {
         println!("Enter your prompt below. Leave it blank to cancel");
+        println!("{MULTILINE_HINT}");
         Self {
-            history: BasicHistory::new().max_entries(8).no_duplicates(true),
+            history,
+            completion: ChatCompletion::default(),
             skip_confirmation,
+            policy,
+            shell,
+            failure_prompt: RefCell::new(None),
         }
     }
 }
 
 impl ChatController for ExecuteLoopController {
     fn create_prompt(&mut self) -> anyhow::Result<Option<String>> {
-        input_chat_prompt(&mut self.history)
+        if let Some(prompt) = self.failure_prompt.get_mut().take() {
+            return Ok(Some(prompt));
+        }
+        input_chat_prompt(&mut self.history, &self.completion)
+    }
+
+    fn on_completion(&self, completion: &str) -> anyhow::Result<()> {
+        let command = strip_code_fences(completion);
+        println!("{command}");
+
+        if !confirm_command(&self.policy, &command, self.skip_confirmation)? {
+            return Ok(());
+        }
+
+        let output = execute(&self.shell, &command)?;
+        if !output.success() && confirm("Do you want to send the error to the assistant?")? {
+            let prompt = command_failure_prompt(&command, output.exit_code, &output.stderr);
+            self.failure_prompt.replace(Some(prompt));
+        }
+
+        Ok(())
+    }
+
+    fn on_command_output(&self, output: &str) -> anyhow::Result<()> {
+        println!("{output}");
+        Ok(())
+    }
+}
+
+/// Controller for chats used only for single completions, see [`lib::Chat::complete`]
+pub(crate) struct OneShotController;
+
+impl ChatController for OneShotController {
+    fn create_prompt(&mut self) -> anyhow::Result<Option<String>> {
+        Ok(None)
+    }
+
+    fn on_completion(&self, _completion: &str) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn on_command_output(&self, output: &str) -> anyhow::Result<()> {
+        eprintln!("{output}");
+        Ok(())
+    }
+}
+
+/// Sends single prompt and prints only the command, everything else goes to stderr
+pub(crate) struct PrintCommandController {
+    prompt: Option<String>,
+}
+
+impl PrintCommandController {
+    pub(crate) fn new(prompt: String) -> Self {
+        Self {
+            prompt: Some(prompt),
+        }
+    }
+}
+
+impl ChatController for PrintCommandController {
+    fn create_prompt(&mut self) -> anyhow::Result<Option<String>> {
+        Ok(self.prompt.take())
+    }
+
+    fn on_completion(&self, completion: &str) -> anyhow::Result<()> {
+        println!("{}", strip_code_fences(completion));
+        Ok(())
+    }
+
+    fn on_command_output(&self, output: &str) -> anyhow::Result<()> {
+        eprintln!("{output}");
+        Ok(())
+    }
+}
+
+/// Sends single prompt with the command and prints the explanation
+pub(crate) struct ExplainController {
+    command: Option<String>,
+    policy: CommandPolicy,
+    render_markdown: bool,
+}
+
+impl ExplainController {
+    pub(crate) fn new(command: String, policy: CommandPolicy, raw: bool) -> Self {
+        Self {
+            command: Some(command),
+            policy,
+            render_markdown: !raw && stdout().is_terminal(),
+        }
+    }
+}
+
+impl ChatController for ExplainController {
+    fn create_prompt(&mut self) -> anyhow::Result<Option<String>> {
+        let Some(command) = self.command.take() else {
+            return Ok(None);
+        };
+
+        println!("{}", Style::new().bold().apply_to(&command));
+        print_assessment(&self.policy.assess(&command));
+
+        Ok(Some(format!("```\n{command}\n```")))
+    }
+
+    fn on_completion(&self, completion: &str) -> anyhow::Result<()> {
+        match self.render_markdown {
+            true => println!("{}", render_markdown(completion)),
+            false => println!("{completion}"),
+        }
+        Ok(())
+    }
+
+    fn on_command_output(&self, output: &str) -> anyhow::Result<()> {
+        println!("{output}");
+        Ok(())
+    }
+}
+
+pub(crate) struct EditLoopController {
+    history: PersistentHistory,
+    completion: ChatCompletion,
+    referenced_paths: Vec<String>,
+    backups: RefCell<Vec<EditBackup>>,
+}
+
+impl EditLoopController {
+    pub(crate) fn new(history: PersistentHistory) -> Self {
+        println!(
+            "Reference files to edit with placeholders, e.g. rename this function: {{src/main.rs}}"
+        );
+        println!(
+            "Leave prompt blank to cancel, type {REVERT_COMMAND} to undo the last applied changes"
+        );
+        println!("{MULTILINE_HINT}");
+        Self {
+            history,
+            completion: ChatCompletion::default(),
+            referenced_paths: vec![],
+            backups: RefCell::new(vec![]),
+        }
+    }
+
+    fn revert(&self) -> anyhow::Result<()> {
+        let Some(backup) = self.backups.borrow_mut().pop() else {
+            println!("Nothing to revert");
+            return Ok(());
+        };
+
+        let paths = backup.revert()?;
+        println!("Reverted changes in {}", paths.join(", "));
+        Ok(())
+    }
+}
+
+impl ChatController for EditLoopController {
+    fn create_prompt(&mut self) -> anyhow::Result<Option<String>> {
+        loop {
+            let Some(prompt) = input_chat_prompt(&mut self.history, &self.completion)? else {
+                return Ok(None);
+            };
+
+            if prompt.trim() == REVERT_COMMAND {
+                self.revert()?;
+                continue;
+            }
+
+            for path in placeholder_file_paths(&prompt) {
+                if !self.referenced_paths.contains(&path) {
+                    self.referenced_paths.push(path);
+                }
+            }
+
+            return Ok(Some(label_file_placeholders(&prompt)));
+        }
     }
 
     fn on_completion(&self, completion: &str) -> anyhow::Result<()> {
-        println!("{completion}");
+        println!("{}", render_markdown(completion));
+
+        let edits = match propose_file_edits(completion, &self.referenced_paths) {
+            Ok(edits) => edits,
+            Err(err) => {
+                eprintln!("Proposed changes are invalid, ask for a fix: {err:#}");
+                return Ok(());
+            }
+        };
+        if edits.is_empty() {
+            println!("No file changes proposed");
+            return Ok(());
+        }
 
-        if self.skip_confirmation || confirm_execute()? {
-            return execute(&completion);
+        edits.iter().for_each(print_file_diff);
+        if !confirm("Do you want to apply these changes?")? {
+            return Ok(());
         }
 
+        let backup = EditBackup::apply(&edits, Storage::edit_backup_dir()?)?;
+        println!(
+            "Changes applied, originals backed up in {:?}, type {REVERT_COMMAND} to undo",
+            backup.dir()
+        );
+        self.backups.borrow_mut().push(backup);
+
+        Ok(())
+    }
+
+    fn on_command_output(&self, output: &str) -> anyhow::Result<()> {
+        println!("{output}");
         Ok(())
     }
 }
 
-fn execute(completion: &str) -> anyhow::Result<()> {
-    let (shell, command_flag) = match env::consts::OS {
-        "windows" => ("powershell", "-Command"),
-        "linux" => ("/bin/sh", "-c"),
-        _ => bail!("your system is not yet supported"),
+/// Prints risk of the command and asks to run it, denied commands never run
+/// and high risk ones need typed confirmation even when confirmation is skipped
+pub(crate) fn confirm_command(
+    policy: &CommandPolicy,
+    command: &str,
+    skip_confirmation: bool,
+) -> anyhow::Result<bool> {
+    let assessment = policy.assess(command);
+    print_assessment(&assessment);
+
+    let confirmed = match assessment.risk {
+        RiskLevel::Denied => {
+            println!("Command was not executed");
+            false
+        }
+        RiskLevel::High => confirm_typed("This command is high risk.", "yes")?,
+        RiskLevel::Low | RiskLevel::Medium => skip_confirmation || confirm_execute()?,
     };
+    Ok(confirmed)
+}
 
-    let output = Command::new(shell)
-        .args(&[command_flag, &completion])
+fn print_assessment(assessment: &CommandAssessment) {
+    let style = match assessment.risk {
+        RiskLevel::Low => Style::new(),
+        RiskLevel::Medium => Style::new().yellow(),
+        RiskLevel::High | RiskLevel::Denied => Style::new().red().bold(),
+    };
+    for reason in &assessment.reasons {
+        println!(
+            "{}",
+            style.apply_to(format!("[{} risk] {reason}", assessment.risk))
+        );
+    }
+}
+
+pub(crate) struct CommandOutput {
+    /// `None` when the command was terminated by a signal
+    pub(crate) exit_code: Option<i32>,
+    pub(crate) stderr: String,
+}
+
+impl CommandOutput {
+    pub(crate) fn success(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+}
+
+/// Runs command with stdout going straight to terminal, stderr is printed as it comes and captured
+pub(crate) fn execute(shell: &str, command: &str) -> anyhow::Result<CommandOutput> {
+    let mut child = shell_command(shell, command)
+        .stderr(Stdio::piped())
         .spawn()
-        .context("Failed to execute command")?
-        .wait_with_output()?;
+        .context("Failed to execute command")?;
 
-    if !output.stdout.is_empty() {
-        println!("{:?}", output.stdout);
+    let mut stderr = String::new();
+    if let Some(child_stderr) = child.stderr.take() {
+        let mut reader = BufReader::new(child_stderr);
+        let mut line = vec![];
+        while reader.read_until(b'\n', &mut line)? > 0 {
+            let text = String::from_utf8_lossy(&line);
+            eprint!("{text}");
+            stderr.push_str(&text);
+            line.clear();
+        }
     }
 
-    if !output.stderr.is_empty() {
-        eprintln!("{:?}", output.stderr);
+    let status = child.wait()?;
+    let output = CommandOutput {
+        exit_code: status.code(),
+        stderr,
+    };
+    if !output.success() {
+        let message = match output.exit_code {
+            Some(code) => format!("Command exited with code {code}"),
+            None => "Command was terminated by a signal".to_owned(),
+        };
+        eprintln!("{}", Style::new().red().apply_to(message));
     }
 
-    Ok(())
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_print_command_controller_sends_prompt_once() {
+        let mut controller = PrintCommandController::new("list files".to_owned());
+
+        assert_eq!(
+            controller.create_prompt().unwrap().as_deref(),
+            Some("list files")
+        );
+        assert_eq!(controller.create_prompt().unwrap(), None);
+    }
 }
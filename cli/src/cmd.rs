@@ -1,12 +1,26 @@
 mod assistant;
 mod chat;
 mod config;
+mod edit;
+mod explain;
+mod git;
+mod history;
+mod review;
 mod shell;
+mod shell_init;
+mod usage;
 
 use crate::cmd::assistant::{match_assistant_cmd, AssistantCommand};
 use crate::cmd::chat::{chat_cmd, ChatArgs};
 use crate::cmd::config::{config_cmd, ConfigArgs};
+use crate::cmd::edit::{edit_cmd, EditArgs};
+use crate::cmd::explain::{explain_cmd, ExplainArgs};
+use crate::cmd::git::{commit_cmd, pr_description_cmd, CommitArgs, PrDescriptionArgs};
+use crate::cmd::history::{match_history_cmd, HistoryCommand};
+use crate::cmd::review::{review_cmd, ReviewArgs};
 use crate::cmd::shell::{shell_cmd, ShellArgs};
+use crate::cmd::shell_init::{shell_init_cmd, ShellInitArgs};
+use crate::cmd::usage::{usage_cmd, UsageArgs};
 use clap::Subcommand;
 
 #[derive(Debug, Subcommand)]
@@ -16,18 +30,43 @@ pub enum Command {
     Assistant(AssistantCommand),
     #[command(about = "Create chat completion")]
     Chat(ChatArgs),
+    #[command(about = "Commit staged changes with generated message")]
+    Commit(CommitArgs),
     #[command(about = "Initialize or update config file")]
     Config(ConfigArgs),
+    #[command(about = "Edit files referenced in prompts with reviewed changes")]
+    Edit(EditArgs),
+    #[command(about = "Explain shell command part by part")]
+    Explain(ExplainArgs),
+    #[command(subcommand)]
+    #[command(about = "Manage chat history")]
+    History(HistoryCommand),
+    #[command(about = "Generate pull request description from branch changes")]
+    PrDescription(PrDescriptionArgs),
+    #[command(about = "Review changes or files and report findings")]
+    Review(ReviewArgs),
     #[command(name = "sh", about = "Execute shell command")]
     Shell(ShellArgs),
+    #[command(about = "Print shell widget generating commands on the command line")]
+    ShellInit(ShellInitArgs),
+    #[command(about = "Report token usage and cost against budgets")]
+    Usage(UsageArgs),
 }
 
 pub async fn match_cmd(command: Command) -> anyhow::Result<()> {
     match command {
         Command::Assistant(command) => match_assistant_cmd(command).await?,
         Command::Chat(args) => chat_cmd(args).await?,
+        Command::Commit(args) => commit_cmd(args).await?,
         Command::Config(args) => config_cmd(args).await?,
+        Command::Edit(args) => edit_cmd(args).await?,
+        Command::Explain(args) => explain_cmd(args).await?,
+        Command::History(command) => match_history_cmd(command).await?,
+        Command::PrDescription(args) => pr_description_cmd(args).await?,
+        Command::Review(args) => review_cmd(args).await?,
         Command::Shell(args) => shell_cmd(args).await?,
+        Command::ShellInit(args) => shell_init_cmd(args).await?,
+        Command::Usage(args) => usage_cmd(args).await?,
     }
     Ok(())
 }
@@ -18,7 +18,7 @@ pub(crate) async fn assistant_add_cmd(mut args: AssistantAddArgs) -> anyhow::Res
     let config = Storage::config()?.read()?;
 
     if args.model.is_none() {
-        let models = OpenAi::new(&config.api_key()).chat_models().await?;
+        let models = OpenAi::new(&config.api_key()?).chat_models().await?;
         let model = select_model(models)?;
         args.model = Some(model);
     }
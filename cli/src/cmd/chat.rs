@@ -1,9 +1,18 @@
 use crate::chat_controller::ChatLoopController;
-use crate::dialog::select_assistant;
+use crate::completion::ChatCompletion;
+use crate::dialog::{select_assistant, EDIT_COMMAND};
+use crate::history::PersistentHistory;
 use crate::storage::Storage;
 use anyhow::{Context, Result};
 use clap::Args;
-use lib::{ChatAssistant, OpenAi};
+use lib::{
+    user_shell, ArgumentCompletionProvider, ChatAssistant, CommandCompletionProvider, OpenAi,
+    CHAT_COMMANDS,
+};
+
+const ASSISTANT_COMMAND: &str = "/assistant";
+const MODEL_COMMAND: &str = "/model";
+const CHAT_HISTORY: &str = "chat";
 
 #[derive(Debug, Args)]
 pub struct ChatArgs {
@@ -11,6 +20,14 @@ pub struct ChatArgs {
     assistant_name: Option<String>,
     #[arg(long, short)]
     thread: bool,
+    #[arg(
+        long,
+        conflicts_with = "thread",
+        help = "id of the conversation from \"history list\" to continue"
+    )]
+    resume: Option<String>,
+    #[arg(long, help = "print completions without markdown rendering")]
+    raw: bool,
 }
 
 pub(crate) async fn chat_cmd(args: ChatArgs) -> Result<()> {
@@ -22,40 +39,81 @@ pub(crate) async fn chat_cmd(args: ChatArgs) -> Result<()> {
 
 pub(crate) async fn chat(args: ChatArgs) -> Result<()> {
     let config = Storage::config()?.read()?;
-    let open_ai = OpenAi::new(&config.api_key());
+    let open_ai = OpenAi::new(&config.api_key()?);
 
     let mut assistants = Storage::assistants()?.list()?;
-    let mut open_ai_assistants = open_ai.assistants().list().await?;
-    assistants.append(&mut open_ai_assistants);
-
-    let assistant = get_or_select_assistant(args.assistant_name, assistants)?;
+    let open_ai_assistants = open_ai.assistants();
+    let (open_ai_assistants, models) =
+        tokio::join!(open_ai_assistants.list(), open_ai.chat_models());
+    assistants.append(&mut open_ai_assistants?);
 
-    let chat_record = open_ai
-        .chat(ChatLoopController::default())
-        .create_loop(&config, &assistant)
-        .await?;
+    // model names only help completion, chat works without them
+    let models = models.unwrap_or_else(|err| {
+        eprintln!("failed to list models for completion: {err:#}");
+        vec![]
+    });
+    let completion = chat_completion(&assistants, &models);
+    let history = PersistentHistory::load(CHAT_HISTORY, config.history_size())?;
+    let resumed = match &args.resume {
+        Some(id) => Some(
+            Storage::chat_records()?
+                .get(id)?
+                .with_context(|| format!("conversation \"{id}\" not found"))?,
+        ),
+        None => None,
+    };
+    // imported conversations have no matching assistant, so one is selected instead
+    let assistant_name = args.assistant_name.or_else(|| {
+        resumed
+            .as_ref()
+            .map(|record| record.assistant_name().to_owned())
+            .filter(|name| assistants.iter().any(|a| a.name() == name))
+    });
+    let assistant = get_or_select_assistant(assistant_name, assistants.clone())?;
 
-    Storage::chat_records()?.save(chat_record)?;
+    let mut chat = open_ai
+        .chat(ChatLoopController::new(
+            completion,
+            history,
+            args.raw || config.raw_output(),
+            config.command_policy(),
+            user_shell(config.shell()),
+        ))
+        .with_assistants(assistants)
+        .with_recorder(Storage::chat_records()?.recorder())
+        .with_usage_tracker(Storage::usage(&config)?);
+    if let Some(record) = resumed {
+        chat = chat.with_resumed(record);
+    }
+    chat.create_loop(&config, &assistant).await?;
 
     Ok(())
 }
 
 async fn chat_thread(args: ChatArgs) -> Result<()> {
     let config = Storage::config()?.read()?;
-    let open_ai = OpenAi::new(&config.api_key());
+    let open_ai = OpenAi::new(&config.api_key()?);
 
     let assistants = open_ai.assistants().list().await?;
+    let completion = chat_completion(&assistants, &[]);
+    let history = PersistentHistory::load(CHAT_HISTORY, config.history_size())?;
     let assistant = get_or_select_assistant(args.assistant_name, assistants)?
         .external()
         .context("only external assistants can use threads")?;
 
-    let chat_record = open_ai
-        .chat(ChatLoopController::default())
+    open_ai
+        .chat(ChatLoopController::new(
+            completion,
+            history,
+            args.raw || config.raw_output(),
+            config.command_policy(),
+            user_shell(config.shell()),
+        ))
+        .with_recorder(Storage::chat_records()?.recorder())
+        .with_usage_tracker(Storage::usage(&config)?)
         .create_loop_with_thread(&assistant)
         .await?;
 
-    Storage::chat_records()?.save(chat_record)?;
-
     Ok(())
 }
 
@@ -67,9 +125,23 @@ fn get_or_select_assistant(
         None => select_assistant(assistants)?,
         Some(assistant_name) => assistants
             .into_iter()
-            .find(|a| a.name() == &assistant_name)
+            .find(|a| a.name() == assistant_name)
             .context("assistant not found")?,
     };
 
     Ok(assistant)
 }
+
+fn chat_completion(assistants: &[ChatAssistant], models: &[String]) -> ChatCompletion {
+    let assistant_names: Vec<_> = assistants.iter().map(|a| a.name()).collect();
+
+    ChatCompletion::default()
+        .with_provider(ArgumentCompletionProvider::new(MODEL_COMMAND, models))
+        .with_provider(ArgumentCompletionProvider::new(
+            ASSISTANT_COMMAND,
+            &assistant_names,
+        ))
+        .with_provider(CommandCompletionProvider::new(
+            &[CHAT_COMMANDS, &[EDIT_COMMAND]].concat(),
+        ))
+}
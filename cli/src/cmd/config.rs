@@ -3,20 +3,88 @@ use crate::storage::Storage;
 use anyhow::bail;
 use clap::Args;
 use lib::validation::{openai_api_key_format_validator, openai_api_key_request_validator};
-use lib::ExpliceConfigStorage;
+use lib::{env_api_key, ExpliceConfigStorage, ExpliceConfigUpdate, StorageBackend};
 use persist::LocalJsonStorage;
 
 #[derive(Debug, Args)]
 pub struct ConfigArgs {
     #[arg(long, short)]
     api_key: Option<String>,
+    #[arg(
+        long,
+        help = "command printing API key instead of storing it, e.g. \"pass show openai\", empty to remove"
+    )]
+    api_key_cmd: Option<String>,
+    #[arg(
+        long,
+        help = "keep API key in Secret Service keyring instead of config file"
+    )]
+    keyring: Option<bool>,
     #[arg(long, short)]
     token_limit: Option<u16>,
+    #[arg(long, help = "number of prompts kept in history per command")]
+    history_size: Option<u16>,
+    #[arg(long, help = "print completions without markdown rendering")]
+    raw_output: Option<bool>,
+    #[arg(
+        long,
+        value_delimiter = ',',
+        help = "comma separated commands trusted in shell mode, e.g. \"git status,cargo\""
+    )]
+    shell_allow: Option<Vec<String>>,
+    #[arg(
+        long,
+        value_delimiter = ',',
+        help = "comma separated commands never executed in shell mode"
+    )]
+    shell_deny: Option<Vec<String>>,
+    #[arg(
+        long,
+        help = "shell running commands, e.g. /usr/local/bin/fish, empty to detect from $SHELL"
+    )]
+    shell: Option<String>,
+    #[arg(
+        long,
+        help = "where assistants, chat records and prompt history are kept: json or sqlite, switching to sqlite imports JSON files"
+    )]
+    storage: Option<StorageBackend>,
+    #[arg(long, help = "USD spent per day before completions warn, 0 to remove")]
+    budget_daily_soft: Option<f64>,
+    #[arg(
+        long,
+        help = "USD spent per day before completions are refused, 0 to remove"
+    )]
+    budget_daily_hard: Option<f64>,
+    #[arg(
+        long,
+        help = "USD spent per month before completions warn, 0 to remove"
+    )]
+    budget_monthly_soft: Option<f64>,
+    #[arg(
+        long,
+        help = "USD spent per month before completions are refused, 0 to remove"
+    )]
+    budget_monthly_hard: Option<f64>,
 }
 
-impl ConfigArgs {
-    pub(crate) fn are_empty(&self) -> bool {
-        self.api_key.is_none() && self.token_limit.is_none()
+impl From<ConfigArgs> for ExpliceConfigUpdate {
+    fn from(args: ConfigArgs) -> Self {
+        Self {
+            api_key: args.api_key,
+            api_key_cmd: args.api_key_cmd,
+            api_key_keyring: args.keyring,
+            token_limit: args.token_limit,
+            history_size: args.history_size,
+            raw_output: args.raw_output,
+            shell_allow: args.shell_allow,
+            shell_deny: args.shell_deny,
+            shell: args.shell,
+            storage_backend: args.storage,
+            budget_daily_soft: args.budget_daily_soft,
+            budget_daily_hard: args.budget_daily_hard,
+            budget_monthly_soft: args.budget_monthly_soft,
+            budget_monthly_hard: args.budget_monthly_hard,
+        }
     }
 }
 
@@ -34,16 +102,23 @@ async fn update_config(
     args: ConfigArgs,
     config_storage: ExpliceConfigStorage<LocalJsonStorage>,
 ) -> anyhow::Result<()> {
-    if args.are_empty() {
+    let update = ExpliceConfigUpdate::from(args);
+    if update.is_empty() {
         bail!("Config exists, provide some arguments for update");
     }
 
-    if let Some(api_key) = &args.api_key {
-        openai_api_key_format_validator(&api_key)?;
-        openai_api_key_request_validator(&api_key).await?;
+    if let Some(api_key) = &update.api_key {
+        openai_api_key_format_validator(api_key)?;
+        openai_api_key_request_validator(api_key).await?;
     };
 
-    config_storage.update(args.api_key.as_deref(), args.token_limit)?;
+    if update.storage_backend == Some(StorageBackend::Sqlite) {
+        for (file_name, imported) in Storage::migrate_to_sqlite()? {
+            println!("Imported {imported} entries from {file_name}");
+        }
+    }
+
+    config_storage.update(update)?;
 
     println!("Successfully updated config");
     Ok(())
@@ -53,17 +128,42 @@ async fn create_config(
     args: ConfigArgs,
     config_storage: ExpliceConfigStorage<LocalJsonStorage>,
 ) -> anyhow::Result<()> {
+    // key printed by command or set in environment doesn't have to be stored
     let api_key = match args.api_key {
+        None if args.api_key_cmd.is_some() || env_api_key().is_some() => String::new(),
         None => input_api_key()?,
         Some(api_key) => api_key,
     };
 
-    openai_api_key_format_validator(&api_key)?;
-    openai_api_key_request_validator(&api_key).await?;
+    if !api_key.is_empty() {
+        openai_api_key_format_validator(&api_key)?;
+        openai_api_key_request_validator(&api_key).await?;
+    }
 
     let token_limit = args.token_limit.unwrap_or(40);
+    // key for keyring never touches the config file
+    let (file_api_key, keyring_api_key) = match args.keyring {
+        Some(true) => (String::new(), Some(api_key)),
+        _ => (api_key, None),
+    };
 
-    config_storage.init(api_key, token_limit)?;
+    config_storage.init(file_api_key, token_limit)?;
+    config_storage.update(ExpliceConfigUpdate {
+        api_key: keyring_api_key,
+        api_key_cmd: args.api_key_cmd,
+        api_key_keyring: args.keyring,
+        history_size: args.history_size,
+        raw_output: args.raw_output,
+        shell_allow: args.shell_allow,
+        shell_deny: args.shell_deny,
+        shell: args.shell,
+        storage_backend: args.storage,
+        budget_daily_soft: args.budget_daily_soft,
+        budget_daily_hard: args.budget_daily_hard,
+        budget_monthly_soft: args.budget_monthly_soft,
+        budget_monthly_hard: args.budget_monthly_hard,
+        ..Default::default()
+    })?;
     Storage::assistants()?.init()?;
 
     println!("Successfully initialized");
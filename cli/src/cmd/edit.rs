@@ -0,0 +1,33 @@
+use crate::chat_controller::EditLoopController;
+use crate::history::PersistentHistory;
+use crate::storage::Storage;
+use anyhow::Result;
+use clap::Args;
+use lib::predefined::edit_assistant;
+use lib::{ChatAssistant, OpenAi};
+
+const EDIT_HISTORY: &str = "edit";
+
+#[derive(Debug, Args)]
+pub struct EditArgs {
+    #[arg(long, short)]
+    model: Option<String>,
+}
+
+pub(crate) async fn edit_cmd(args: EditArgs) -> Result<()> {
+    let config = Storage::config()?.read()?;
+    let mut assistant = edit_assistant();
+    if let Some(model) = &args.model {
+        assistant = assistant.with_model(model);
+    }
+
+    let history = PersistentHistory::load(EDIT_HISTORY, config.history_size())?;
+    OpenAi::new(&config.api_key()?)
+        .chat(EditLoopController::new(history))
+        .with_recorder(Storage::chat_records()?.recorder())
+        .with_usage_tracker(Storage::usage(&config)?)
+        .create_loop(&config, &ChatAssistant::LocalAssistant(assistant))
+        .await?;
+
+    Ok(())
+}
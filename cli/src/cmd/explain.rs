@@ -0,0 +1,38 @@
+use crate::chat_controller::ExplainController;
+use crate::storage::Storage;
+use anyhow::{Context, Result};
+use clap::Args;
+use lib::predefined::explain_assistant;
+use lib::{ChatAssistant, OpenAi, ShellEnvironment, APP_NAME};
+
+#[derive(Debug, Args)]
+pub struct ExplainArgs {
+    #[arg(help = "command to explain, defaults to the last command from your shell history")]
+    command: Option<String>,
+    #[arg(long, help = "print explanation without markdown rendering")]
+    raw: bool,
+}
+
+pub(crate) async fn explain_cmd(args: ExplainArgs) -> Result<()> {
+    let config = Storage::config()?.read()?;
+    let environment = ShellEnvironment::detect(config.shell());
+    let command = match args.command {
+        Some(command) => command,
+        None => environment
+            .last_history_command(APP_NAME)?
+            .context("no command given and shell history is empty or not found")?,
+    };
+
+    let assistant = ChatAssistant::LocalAssistant(explain_assistant(&environment));
+    OpenAi::new(&config.api_key()?)
+        .chat(ExplainController::new(
+            command,
+            config.command_policy(),
+            args.raw || config.raw_output(),
+        ))
+        .with_usage_tracker(Storage::usage(&config)?)
+        .create_loop(&config, &assistant)
+        .await?;
+
+    Ok(())
+}
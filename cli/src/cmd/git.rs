@@ -0,0 +1,136 @@
+use crate::chat_controller::OneShotController;
+use crate::dialog::{edit_text, select_commit_action, CommitAction};
+use crate::storage::Storage;
+use anyhow::{bail, Result};
+use clap::Args;
+use lib::predefined::{commit_message_assistant, diff_summary_assistant, pr_description_assistant};
+use lib::{
+    branch_diff, branch_log, chunk_diff, commit, staged_diff, strip_enclosing_code_fence, Chat,
+    ChatAssistant, ChatController, ExpliceConfig, LocalChatAssistant, OpenAi,
+};
+
+/// Diffs larger than this are summarized chunk by chunk before generating the message
+const DIFF_TOKEN_BUDGET: usize = 6000;
+
+#[derive(Debug, Args)]
+pub struct CommitArgs {
+    #[arg(long, short)]
+    model: Option<String>,
+    #[arg(long, short, help = "commit generated message without review")]
+    yes: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct PrDescriptionArgs {
+    #[arg(help = "branch the current branch will be merged into")]
+    base: String,
+    #[arg(long, short)]
+    model: Option<String>,
+}
+
+pub(crate) async fn commit_cmd(args: CommitArgs) -> Result<()> {
+    let diff = staged_diff()?;
+    if diff.trim().is_empty() {
+        bail!("no staged changes, stage them with \"git add\" first");
+    }
+
+    let config = Storage::config()?.read()?;
+    let open_ai = OpenAi::new(&config.api_key()?);
+    let mut chat = open_ai
+        .chat(OneShotController)
+        .with_usage_tracker(Storage::usage(&config)?);
+    let assistant = with_model(commit_message_assistant(), args.model.as_deref());
+
+    let mut message = commit_message(&mut chat, &config, &assistant, &diff).await?;
+    if args.yes {
+        return commit(&message);
+    }
+
+    loop {
+        println!("\n{message}\n");
+        match select_commit_action()? {
+            CommitAction::Commit => return commit(&message),
+            CommitAction::Edit => {
+                if let Some(edited) = edit_text(&message)? {
+                    message = edited;
+                }
+            }
+            CommitAction::Regenerate => {
+                message = commit_message(&mut chat, &config, &assistant, &diff).await?;
+            }
+            CommitAction::Cancel => return Ok(()),
+        }
+    }
+}
+
+pub(crate) async fn pr_description_cmd(args: PrDescriptionArgs) -> Result<()> {
+    let diff = branch_diff(&args.base)?;
+    if diff.trim().is_empty() {
+        bail!("no changes between {} and the current branch", args.base);
+    }
+    let log = branch_log(&args.base)?;
+
+    let config = Storage::config()?.read()?;
+    let open_ai = OpenAi::new(&config.api_key()?);
+    let mut chat = open_ai
+        .chat(OneShotController)
+        .with_usage_tracker(Storage::usage(&config)?);
+    let assistant = with_model(pr_description_assistant(), args.model.as_deref());
+
+    let context = format!("Commit messages:\n{log}\n");
+    let description = generate(&mut chat, &config, &assistant, &context, &diff).await?;
+    println!("{description}");
+
+    Ok(())
+}
+
+async fn commit_message<C: ChatController>(
+    chat: &mut Chat<'_, C>,
+    config: &ExpliceConfig,
+    assistant: &ChatAssistant,
+    diff: &str,
+) -> Result<String> {
+    let completion = generate(chat, config, assistant, "", diff).await?;
+    Ok(strip_enclosing_code_fence(&completion))
+}
+
+fn with_model(assistant: LocalChatAssistant, model: Option<&str>) -> ChatAssistant {
+    let assistant = match model {
+        Some(model) => assistant.with_model(model),
+        None => assistant,
+    };
+    ChatAssistant::LocalAssistant(assistant)
+}
+
+/// Sends diff with context to assistant, diffs over the budget are summarized in chunks first
+async fn generate<C: ChatController>(
+    chat: &mut Chat<'_, C>,
+    config: &ExpliceConfig,
+    assistant: &ChatAssistant,
+    context: &str,
+    diff: &str,
+) -> Result<String> {
+    let chunks = chunk_diff(diff, DIFF_TOKEN_BUDGET);
+    let changes = match chunks.len() {
+        0 | 1 => format!("Diff:\n{diff}"),
+        count => {
+            let summary_assistant = ChatAssistant::LocalAssistant(
+                diff_summary_assistant().with_model(assistant.model()),
+            );
+            let mut summaries = vec![];
+            for (index, chunk) in chunks.iter().enumerate() {
+                eprintln!("Summarizing diff part {} of {count}", index + 1);
+                summaries.push(chat.complete(config, &summary_assistant, chunk).await?);
+            }
+            format!(
+                "Summary of the diff, too large to include:\n{}",
+                summaries.join("\n")
+            )
+        }
+    };
+
+    let completion = chat
+        .complete(config, assistant, &format!("{context}{changes}"))
+        .await?;
+    Ok(completion.trim().to_owned())
+}
@@ -0,0 +1,32 @@
+mod compact;
+mod export;
+mod import;
+mod list;
+
+use crate::cmd::history::compact::history_compact_cmd;
+use crate::cmd::history::export::{history_export_cmd, HistoryExportArgs};
+use crate::cmd::history::import::{history_import_cmd, HistoryImportArgs};
+use crate::cmd::history::list::{history_list_cmd, HistoryListArgs};
+use clap::Subcommand;
+
+#[derive(Debug, Subcommand)]
+pub enum HistoryCommand {
+    #[command(about = "List conversations with their ids")]
+    List(HistoryListArgs),
+    #[command(about = "Export conversations as Markdown, HTML, JSON or fine-tuning JSONL")]
+    Export(HistoryExportArgs),
+    #[command(about = "Import conversations from ChatGPT data export")]
+    Import(HistoryImportArgs),
+    #[command(about = "Rewrite chat history file without updated and deleted records")]
+    Compact,
+}
+
+pub(crate) async fn match_history_cmd(command: HistoryCommand) -> anyhow::Result<()> {
+    match command {
+        HistoryCommand::List(args) => history_list_cmd(args).await?,
+        HistoryCommand::Export(args) => history_export_cmd(args).await?,
+        HistoryCommand::Import(args) => history_import_cmd(args).await?,
+        HistoryCommand::Compact => history_compact_cmd().await?,
+    }
+    Ok(())
+}
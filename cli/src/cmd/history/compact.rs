@@ -0,0 +1,11 @@
+use crate::storage::Storage;
+
+pub(crate) async fn history_compact_cmd() -> anyhow::Result<()> {
+    let stats = Storage::compact_chat_records()?;
+
+    println!(
+        "Compacted chat history from {} to {} lines",
+        stats.lines_before, stats.lines_after
+    );
+    Ok(())
+}
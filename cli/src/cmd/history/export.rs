@@ -0,0 +1,66 @@
+use crate::storage::Storage;
+use anyhow::{Context, Result};
+use clap::{Args, ValueEnum};
+use lib::{export_records, ExportFormat};
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, ValueEnum)]
+enum HistoryExportFormat {
+    Markdown,
+    Html,
+    Json,
+    /// OpenAI chat fine-tuning JSONL with system prompts, skips sessions in progress
+    /// and ones that changed their system prompt
+    Finetune,
+}
+
+impl From<HistoryExportFormat> for ExportFormat {
+    fn from(format: HistoryExportFormat) -> Self {
+        match format {
+            HistoryExportFormat::Markdown => ExportFormat::Markdown,
+            HistoryExportFormat::Html => ExportFormat::Html,
+            HistoryExportFormat::Json => ExportFormat::Json,
+            HistoryExportFormat::Finetune => ExportFormat::FineTune,
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct HistoryExportArgs {
+    #[arg(
+        required_unless_present = "all",
+        conflicts_with = "all",
+        help = "id of the conversation from \"history list\""
+    )]
+    id: Option<String>,
+    #[arg(long, help = "export all conversations")]
+    all: bool,
+    #[arg(long, short, value_enum, default_value = "markdown")]
+    format: HistoryExportFormat,
+    #[arg(long, short, help = "file to write to instead of standard output")]
+    output: Option<PathBuf>,
+}
+
+pub(crate) async fn history_export_cmd(args: HistoryExportArgs) -> Result<()> {
+    let chat_records = Storage::chat_records()?;
+    let records = match args.id {
+        Some(id) => {
+            let record = chat_records
+                .get(&id)?
+                .with_context(|| format!("conversation \"{id}\" not found"))?;
+            vec![(id, record)]
+        }
+        None => chat_records.list()?,
+    };
+
+    let export = export_records(&records, args.format.into())?;
+    match args.output {
+        Some(path) => {
+            fs::write(&path, export).with_context(|| format!("failed to write {path:?}"))?;
+            eprintln!("Exported {} conversations to {path:?}", records.len());
+        }
+        None => println!("{export}"),
+    }
+    Ok(())
+}
@@ -0,0 +1,25 @@
+use crate::storage::Storage;
+use clap::Args;
+use lib::read_chatgpt_export;
+use std::path::PathBuf;
+
+#[derive(Debug, Args)]
+pub struct HistoryImportArgs {
+    #[arg(help = "conversations.json or the zip archive of ChatGPT data export")]
+    path: PathBuf,
+}
+
+pub(crate) async fn history_import_cmd(args: HistoryImportArgs) -> anyhow::Result<()> {
+    let export = read_chatgpt_export(&args.path)?;
+    for skipped in &export.skipped {
+        eprintln!("skipped {skipped}");
+    }
+    let total = export.records.len();
+    let imported = Storage::chat_records()?.import(export.records)?;
+
+    println!(
+        "Imported {imported} of {total} conversations, {} were imported before",
+        total - imported
+    );
+    Ok(())
+}
@@ -0,0 +1,49 @@
+use crate::storage::Storage;
+use clap::Args;
+use lib::{Role, SessionStatus};
+
+const PROMPT_PREVIEW_CHARS: usize = 60;
+
+#[derive(Debug, Args)]
+pub struct HistoryListArgs {
+    #[arg(
+        long,
+        short,
+        help = "show only conversations containing the text, case insensitive"
+    )]
+    search: Option<String>,
+}
+
+pub(crate) async fn history_list_cmd(args: HistoryListArgs) -> anyhow::Result<()> {
+    let records = Storage::chat_records()?.list()?;
+    let records = records.iter().filter(|(_, record)| match &args.search {
+        Some(query) => record.contains(query),
+        None => true,
+    });
+
+    for (id, record) in records {
+        let prompt = record
+            .messages()
+            .iter()
+            .find(|message| message.role() == Role::User)
+            .map(|message| message.content().lines().next().unwrap_or_default())
+            .unwrap_or_default();
+        let preview: String = record
+            .title()
+            .unwrap_or(prompt)
+            .chars()
+            .take(PROMPT_PREVIEW_CHARS)
+            .collect();
+        let status = match record.status() {
+            SessionStatus::Finished => String::new(),
+            status => format!(" ({status})"),
+        };
+
+        println!(
+            "{id}  {}  {}{status}  {preview}",
+            record.creation_date().format("%Y-%m-%d %H:%M"),
+            record.assistant_name()
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,181 @@
+use crate::chat_controller::OneShotController;
+use crate::storage::Storage;
+use anyhow::{bail, Result};
+use clap::{Args, ValueEnum};
+use console::Style;
+use lib::predefined::review_assistant;
+use lib::{
+    chunk_diff, diff, estimate_tokens, is_revision, number_lines, parse_review_findings, read_file,
+    ChatAssistant, OpenAi, ReviewFinding, Severity,
+};
+
+const DIFF_CONTEXT_LINES: u32 = 10;
+/// Diffs and files larger than this are reviewed in separate requests
+const REVIEW_TOKEN_BUDGET: usize = 8000;
+
+#[derive(Debug, Clone, ValueEnum)]
+enum ReviewFormat {
+    Text,
+    Json,
+    /// Workflow commands creating annotations in GitHub Actions
+    Github,
+}
+
+#[derive(Debug, Args)]
+pub struct ReviewArgs {
+    #[arg(
+        help = "revision range like main..HEAD or paths of files, uncommitted changes by default"
+    )]
+    targets: Vec<String>,
+    #[arg(long, short)]
+    model: Option<String>,
+    #[arg(long, short, value_enum, default_value = "text")]
+    format: ReviewFormat,
+}
+
+pub(crate) async fn review_cmd(args: ReviewArgs) -> Result<()> {
+    let sources = review_sources(&args.targets)?;
+    if sources.is_empty() {
+        bail!("nothing to review");
+    }
+
+    let config = Storage::config()?.read()?;
+    let open_ai = OpenAi::new(&config.api_key()?);
+    let mut chat = open_ai
+        .chat(OneShotController)
+        .with_usage_tracker(Storage::usage(&config)?);
+    let mut assistant = review_assistant();
+    if let Some(model) = &args.model {
+        assistant = assistant.with_model(model);
+    }
+    let assistant = ChatAssistant::LocalAssistant(assistant);
+
+    let mut findings = vec![];
+    for (index, source) in sources.iter().enumerate() {
+        if sources.len() > 1 {
+            eprintln!("Reviewing part {} of {}", index + 1, sources.len());
+        }
+        let completion = chat.complete(&config, &assistant, source).await?;
+        let mut review = parse_review_findings(&completion)?;
+        for entry in &review.skipped {
+            eprintln!("skipped malformed finding: {entry}");
+        }
+        findings.append(&mut review.findings);
+    }
+
+    match args.format {
+        ReviewFormat::Text => print_findings(&findings),
+        ReviewFormat::Json => println!("{}", serde_json::to_string_pretty(&findings)?),
+        ReviewFormat::Github => findings.iter().for_each(print_github_annotation),
+    }
+
+    Ok(())
+}
+
+/// Prompts with diff chunks for revision range, or numbered file contents for paths
+fn review_sources(targets: &[String]) -> Result<Vec<String>> {
+    let rev_range = match targets {
+        [] => None,
+        [target] if is_revision(target) => Some(target.as_str()),
+        _ => {
+            let files = targets
+                .iter()
+                .map(|path| {
+                    let content = number_lines(&read_file(path)?);
+                    Ok(format!("File {path}:\n{content}\n"))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            return Ok(chunk_files(files));
+        }
+    };
+
+    let diff = diff(rev_range, DIFF_CONTEXT_LINES)?;
+    Ok(chunk_diff(&diff, REVIEW_TOKEN_BUDGET))
+}
+
+/// Groups whole files into chunks under the budget, single file over it gets its own chunk
+fn chunk_files(files: Vec<String>) -> Vec<String> {
+    let mut chunks: Vec<String> = vec![];
+    for file in files {
+        match chunks.last_mut() {
+            Some(chunk)
+                if estimate_tokens(chunk) + estimate_tokens(&file) <= REVIEW_TOKEN_BUDGET =>
+            {
+                chunk.push_str(&file)
+            }
+            _ => chunks.push(file),
+        }
+    }
+    chunks
+}
+
+fn print_findings(findings: &[ReviewFinding]) {
+    if findings.is_empty() {
+        println!("No findings");
+        return;
+    }
+
+    for finding in findings {
+        let style = match finding.severity {
+            Severity::Info => Style::new().cyan(),
+            Severity::Warning => Style::new().yellow(),
+            Severity::Error => Style::new().red().bold(),
+        };
+        let location = match finding.line {
+            Some(line) => format!("{}:{line}", finding.file),
+            None => finding.file.to_owned(),
+        };
+        println!(
+            "{} {} {}",
+            Style::new().bold().apply_to(location),
+            style.apply_to(format!("[{}]", finding.severity)),
+            finding.suggestion
+        );
+    }
+}
+
+fn print_github_annotation(finding: &ReviewFinding) {
+    let command = match finding.severity {
+        Severity::Info => "notice",
+        Severity::Warning => "warning",
+        Severity::Error => "error",
+    };
+    let line = finding
+        .line
+        .map(|line| format!(",line={line}"))
+        .unwrap_or_default();
+    println!(
+        "::{command} file={}{line}::{}",
+        escape_property(&finding.file),
+        escape_data(&finding.suggestion)
+    );
+}
+
+/// Workflow command messages can't contain raw newlines
+fn escape_data(text: &str) -> String {
+    text.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Property values also end at `,` and `:` separating them from the rest of the command
+fn escape_property(text: &str) -> String {
+    escape_data(text).replace(':', "%3A").replace(',', "%2C")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_annotation() {
+        assert_eq!(
+            escape_data("50% done\r\nnext: a, b"),
+            "50%25 done%0D%0Anext: a, b"
+        );
+        assert_eq!(
+            escape_property("C:\\src\\a,b%.rs\n"),
+            "C%3A\\src\\a%2Cb%25.rs%0A"
+        );
+    }
+}
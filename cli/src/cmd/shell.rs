@@ -1,29 +1,56 @@
-use crate::chat_controller::ExecuteLoopController;
+use crate::chat_controller::{ExecuteLoopController, PrintCommandController};
+use crate::history::PersistentHistory;
 use crate::storage::Storage;
-use anyhow::{bail, Result};
+use anyhow::Result;
 use clap::Args;
 use lib::predefined::shell_assistant;
-use lib::{ChatAssistant, OpenAi};
-use std::env;
+use lib::{ChatAssistant, OpenAi, ShellEnvironment};
+
+const SHELL_HISTORY: &str = "sh";
 
 #[derive(Debug, Args)]
 pub struct ShellArgs {
-    #[arg(long, short, help = "execute without confirmation")]
+    #[arg(
+        long,
+        short,
+        help = "execute without confirmation, high risk commands still need to be confirmed"
+    )]
     yes: bool,
+    #[arg(
+        long,
+        requires = "prompt",
+        help = "only print generated command, used by shell widgets"
+    )]
+    print: bool,
+    #[arg(requires = "print", help = "prompt sent without interactive input")]
+    prompt: Option<String>,
 }
 
 pub(crate) async fn shell_cmd(args: ShellArgs) -> Result<()> {
-    let shell = match env::consts::OS {
-        "windows" => "powershell",
-        "linux" => "bash",
-        _ => bail!("your system is not yet supported"),
-    };
-
     let config = Storage::config()?.read()?;
-    let assistant = ChatAssistant::LocalAssistant(shell_assistant(shell));
+    let environment = ShellEnvironment::detect(config.shell());
+    let assistant = ChatAssistant::LocalAssistant(shell_assistant(&environment));
+    let open_ai = OpenAi::new(&config.api_key()?);
+
+    if let (true, Some(prompt)) = (args.print, args.prompt) {
+        open_ai
+            .chat(PrintCommandController::new(prompt))
+            .with_usage_tracker(Storage::usage(&config)?)
+            .create_loop(&config, &assistant)
+            .await?;
+        return Ok(());
+    }
+
+    let history = PersistentHistory::load(SHELL_HISTORY, config.history_size())?;
 
-    OpenAi::new(&config.api_key())
-        .chat(ExecuteLoopController::new(args.yes))
+    open_ai
+        .chat(ExecuteLoopController::new(
+            args.yes,
+            history,
+            config.command_policy(),
+            environment.shell().to_owned(),
+        ))
+        .with_usage_tracker(Storage::usage(&config)?)
         .create_loop(&config, &assistant)
         .await?;
 
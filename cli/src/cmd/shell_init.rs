@@ -0,0 +1,91 @@
+use anyhow::Result;
+use clap::{Args, ValueEnum};
+
+const BASH_WIDGET: &str = r#"# explice widget, replaces typed intent with generated command on Alt+E
+_explice_widget() {
+    [[ -z "$READLINE_LINE" ]] && return
+    local command
+    command="$(explice sh --print "$READLINE_LINE" </dev/tty)" || return
+    READLINE_LINE="$command"
+    READLINE_POINT=${#READLINE_LINE}
+}
+bind -x '"\ee": _explice_widget'
+"#;
+
+const ZSH_WIDGET: &str = r#"# explice widget, replaces typed intent with generated command on Alt+E
+_explice_widget() {
+    [[ -z "$BUFFER" ]] && return
+    local command
+    command="$(explice sh --print "$BUFFER" </dev/tty)" || { zle reset-prompt; return }
+    BUFFER="$command"
+    CURSOR=${#BUFFER}
+    zle reset-prompt
+}
+zle -N _explice_widget
+bindkey '^[e' _explice_widget
+"#;
+
+const FISH_WIDGET: &str = r#"# explice widget, replaces typed intent with generated command on Alt+E
+function _explice_widget
+    set -l intent (commandline)
+    test -z "$intent"; and return
+    set -l command (explice sh --print "$intent" </dev/tty | string collect)
+    and commandline --replace -- $command
+    commandline --function repaint
+end
+bind \ee _explice_widget
+"#;
+
+#[derive(Debug, Clone, ValueEnum)]
+enum WidgetShell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+#[derive(Debug, Args)]
+#[command(
+    after_help = "Add to your shell config:\n  bash: eval \"$(explice shell-init bash)\"\n  zsh:  eval \"$(explice shell-init zsh)\"\n  fish: explice shell-init fish | source"
+)]
+pub struct ShellInitArgs {
+    shell: WidgetShell,
+}
+
+pub(crate) async fn shell_init_cmd(args: ShellInitArgs) -> Result<()> {
+    print!("{}", widget(&args.shell));
+
+    Ok(())
+}
+
+fn widget(shell: &WidgetShell) -> &'static str {
+    match shell {
+        WidgetShell::Bash => BASH_WIDGET,
+        WidgetShell::Zsh => ZSH_WIDGET,
+        WidgetShell::Fish => FISH_WIDGET,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_widget_binds_print_command() {
+        let bindings = [
+            (WidgetShell::Bash, r#"bind -x '"\ee": _explice_widget'"#),
+            (WidgetShell::Zsh, "bindkey '^[e' _explice_widget"),
+            (WidgetShell::Fish, r"bind \ee _explice_widget"),
+        ];
+        assert_eq!(bindings.len(), WidgetShell::value_variants().len());
+
+        for (shell, binding) in bindings {
+            let widget = widget(&shell);
+            assert!(widget.contains(binding), "{shell:?} widget is not bound");
+            assert!(
+                widget.contains("explice sh --print \""),
+                "{shell:?} widget does not print the command"
+            );
+            assert!(widget.contains("</dev/tty"), "{shell:?} widget has no tty");
+        }
+    }
+}
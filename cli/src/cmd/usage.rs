@@ -0,0 +1,98 @@
+use crate::storage::Storage;
+use anyhow::Result;
+use clap::{Args, ValueEnum};
+use console::Style;
+use lib::{model_price, summarize_usage, Budgets, Spending, UsageGroup};
+
+#[derive(Debug, Clone, ValueEnum)]
+enum UsageGrouping {
+    Day,
+    Model,
+    Assistant,
+}
+
+impl From<UsageGrouping> for UsageGroup {
+    fn from(grouping: UsageGrouping) -> Self {
+        match grouping {
+            UsageGrouping::Day => UsageGroup::Day,
+            UsageGrouping::Model => UsageGroup::Model,
+            UsageGrouping::Assistant => UsageGroup::Assistant,
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct UsageArgs {
+    #[arg(
+        long,
+        short,
+        default_value_t = 30,
+        help = "number of days including today"
+    )]
+    days: u32,
+    #[arg(long, short, value_enum, default_value = "model")]
+    by: UsageGrouping,
+}
+
+pub(crate) async fn usage_cmd(args: UsageArgs) -> Result<()> {
+    let config = Storage::config()?.read()?;
+    let usage = Storage::usage(&config)?;
+    let records = usage.list(args.days)?;
+
+    let group = UsageGroup::from(args.by);
+    let summaries = summarize_usage(&records, group);
+    let width = summaries
+        .iter()
+        .map(|summary| summary.group.len())
+        .max()
+        .unwrap_or_default()
+        .max(5);
+
+    println!(
+        "{:<width$}  {:>12}  {:>12}  {:>10}",
+        "", "prompt", "completion", "cost"
+    );
+    for summary in &summaries {
+        let unpriced = group == UsageGroup::Model && model_price(&summary.group).is_none();
+        println!(
+            "{:<width$}  {:>12}  {:>12}  {:>10}",
+            summary.group,
+            summary.usage.prompt_tokens,
+            summary.usage.completion_tokens,
+            match unpriced {
+                true => "no price".to_owned(),
+                false => format!("${:.4}", summary.cost),
+            }
+        );
+    }
+    let total: f64 = summaries.iter().map(|summary| summary.cost).sum();
+    println!("{:<width$}  {:>38}", "total", format!("${total:.4}"));
+
+    println!();
+    print_budgets(&usage.spending()?, config.budgets());
+    Ok(())
+}
+
+fn print_budgets(spending: &Spending, budgets: &Budgets) {
+    println!("Spent {spending}");
+
+    let limits = [
+        ("daily soft", spending.today, budgets.daily_soft),
+        ("daily hard", spending.today, budgets.daily_hard),
+        ("monthly soft", spending.this_month, budgets.monthly_soft),
+        ("monthly hard", spending.this_month, budgets.monthly_hard),
+    ];
+    for (name, spent, limit) in limits {
+        let Some(limit) = limit else {
+            continue;
+        };
+        let style = match spent >= limit {
+            true => Style::new().red(),
+            false => Style::new().green(),
+        };
+        println!(
+            "{name} budget: {}",
+            style.apply_to(format!("${spent:.2} of ${limit:.2}"))
+        );
+    }
+}
@@ -0,0 +1,159 @@
+use crate::chat_controller::{confirm_command, execute};
+use crate::dialog::{confirm, input_file_path, select_code_action, select_code_block};
+use anyhow::{bail, Context, Result};
+use lib::{parse_code_blocks, parse_unified_diff, CodeBlock, CommandPolicy};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+const CLIPBOARD_COMMANDS: &[(&str, &[&str])] = &[
+    ("wl-copy", &[]),
+    ("xclip", &["-selection", "clipboard"]),
+    ("xsel", &["--clipboard", "--input"]),
+    ("pbcopy", &[]),
+    ("clip", &[]),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum CodeAction {
+    Copy,
+    Write,
+    Apply,
+    Run,
+}
+
+impl CodeAction {
+    pub(crate) fn available(block: &CodeBlock) -> Vec<Self> {
+        if block.is_diff() {
+            return vec![Self::Apply, Self::Copy, Self::Write];
+        }
+        if block.is_shell() {
+            return vec![Self::Copy, Self::Write, Self::Run];
+        }
+        vec![Self::Copy, Self::Write]
+    }
+
+    pub(crate) fn label(&self) -> &str {
+        match self {
+            CodeAction::Copy => "Copy to clipboard",
+            CodeAction::Write => "Write to file",
+            CodeAction::Apply => "Apply diff to working tree",
+            CodeAction::Run => "Run in shell",
+        }
+    }
+}
+
+/// Lets user act on code blocks from the completion until they choose to continue the chat
+pub(crate) fn offer_code_actions(
+    completion: &str,
+    policy: &CommandPolicy,
+    shell: &str,
+) -> Result<()> {
+    let blocks = parse_code_blocks(completion);
+    if blocks.is_empty() {
+        return Ok(());
+    }
+
+    while let Some(block) = select_code_block(&blocks)? {
+        let Some(action) = select_code_action(block)? else {
+            continue;
+        };
+
+        if let Err(err) = run_code_action(action, block, policy, shell) {
+            eprintln!("{err:?}");
+        }
+    }
+
+    Ok(())
+}
+
+fn run_code_action(
+    action: CodeAction,
+    block: &CodeBlock,
+    policy: &CommandPolicy,
+    shell: &str,
+) -> Result<()> {
+    match action {
+        CodeAction::Copy => copy_to_clipboard(&block.code),
+        CodeAction::Write => write_to_file(&block.code),
+        CodeAction::Apply => apply_diff(&block.code),
+        // same checks as commands generated by `explice sh`
+        CodeAction::Run => match confirm_command(policy, &block.code, false)? {
+            true => execute(shell, &block.code).map(|_| ()),
+            false => Ok(()),
+        },
+    }
+}
+
+fn copy_to_clipboard(code: &str) -> Result<()> {
+    for (program, args) in CLIPBOARD_COMMANDS {
+        let Ok(mut child) = Command::new(program)
+            .args(*args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        else {
+            continue;
+        };
+
+        child
+            .stdin
+            .take()
+            .context("failed to open clipboard input")?
+            .write_all(code.as_bytes())?;
+
+        if child.wait()?.success() {
+            println!("Copied to clipboard");
+            return Ok(());
+        }
+    }
+
+    bail!("no clipboard tool found, install one of: wl-copy, xclip, xsel")
+}
+
+fn write_to_file(code: &str) -> Result<()> {
+    let path = input_file_path()?;
+    if Path::new(&path).exists() && !confirm(&format!("File {path} exists, overwrite it?"))? {
+        return Ok(());
+    }
+
+    fs::write(&path, code).with_context(|| format!("failed to write {path}"))?;
+    println!("Written to {path}");
+    Ok(())
+}
+
+fn apply_diff(diff: &str) -> Result<()> {
+    let patches = parse_unified_diff(diff)?;
+
+    let mut patched_files = vec![];
+    for patch in &patches {
+        let content = match patch.is_new_file() {
+            true => String::new(),
+            false => fs::read_to_string(patch.path())
+                .with_context(|| format!("failed to read {}", patch.path()))?,
+        };
+        patched_files.push((patch, patch.apply(&content)?));
+    }
+
+    let paths: Vec<_> = patches.iter().map(|patch| patch.path()).collect();
+    if !confirm(&format!("Apply changes to {}?", paths.join(", ")))? {
+        return Ok(());
+    }
+
+    for (patch, content) in patched_files {
+        match patch.is_deleted_file() {
+            true => fs::remove_file(patch.path())?,
+            false => {
+                if let Some(dir) = Path::new(patch.path()).parent() {
+                    fs::create_dir_all(dir)?;
+                }
+                fs::write(patch.path(), content)?;
+            }
+        }
+    }
+
+    println!("Applied changes to {}", paths.join(", "));
+    Ok(())
+}
@@ -1,22 +1,91 @@
 use dialoguer::Completion;
-use lib::get_path_completion;
+use lib::{
+    longest_common_prefix, CompletionProvider, PathCompletionProvider,
+    PlaceholderCompletionProvider,
+};
+use std::cell::RefCell;
 
-pub struct PathCompletion;
+pub struct ChatCompletion {
+    providers: Vec<Box<dyn CompletionProvider>>,
+    state: RefCell<CompletionState>,
+}
+
+#[derive(Default)]
+struct CompletionState {
+    last_completion: Option<String>,
+    candidates: Vec<String>,
+    index: Option<usize>,
+}
 
-impl Default for PathCompletion {
+impl Default for ChatCompletion {
     fn default() -> Self {
-        Self
+        Self {
+            providers: vec![
+                Box::new(PlaceholderCompletionProvider),
+                Box::new(PathCompletionProvider),
+            ],
+            state: Default::default(),
+        }
     }
 }
 
-impl Completion for PathCompletion {
+impl ChatCompletion {
+    /// Provider added later takes precedence over the ones added before
+    pub fn with_provider<P>(mut self, provider: P) -> Self
+    where
+        P: CompletionProvider + 'static,
+    {
+        self.providers.insert(0, Box::new(provider));
+        self
+    }
+
+    fn candidates(&self, input: &str) -> anyhow::Result<Vec<String>> {
+        for provider in &self.providers {
+            let candidates = provider.completions(input)?;
+            if !candidates.is_empty() {
+                return Ok(candidates);
+            }
+        }
+
+        Ok(vec![])
+    }
+}
+
+impl Completion for ChatCompletion {
     fn get(&self, input: &str) -> Option<String> {
-        match get_path_completion(input) {
-            Ok(completion) => Some(completion),
+        let mut state = self.state.borrow_mut();
+
+        if state.last_completion.as_deref() == Some(input) && state.candidates.len() > 1 {
+            let index = state
+                .index
+                .map_or(0, |index| (index + 1) % state.candidates.len());
+            let completion = state.candidates[index].to_owned();
+            state.index = Some(index);
+            state.last_completion = Some(completion.to_owned());
+            return Some(completion);
+        }
+
+        let candidates = match self.candidates(input) {
+            Ok(candidates) => candidates,
             Err(err) => {
                 eprintln!("\n{err:?}");
                 return Some(input.to_owned());
             }
-        }
+        };
+
+        let common_prefix = longest_common_prefix(&candidates);
+        let (completion, index) = match candidates.first() {
+            None => (input.to_owned(), None),
+            Some(_) if common_prefix.len() > input.len() => (common_prefix, None),
+            Some(first) => (first.to_owned(), Some(0)),
+        };
+
+        *state = CompletionState {
+            last_completion: Some(completion.to_owned()),
+            candidates,
+            index,
+        };
+
+        Some(completion)
     }
 }
@@ -1,26 +1,99 @@
-use crate::completion::PathCompletion;
+use crate::code_actions::CodeAction;
+use crate::completion::ChatCompletion;
+use crate::history::PersistentHistory;
+use crate::prompt_input::input_prompt_line;
 use anyhow::Result;
-use dialoguer::{BasicHistory, Confirm, Input, Select};
+use dialoguer::{Confirm, Editor, Input, Select};
 use lib::validation::openai_api_key_format_validator;
-use lib::ChatAssistant;
+use lib::{ChatAssistant, CodeBlock};
 
-pub fn input_chat_prompt(history: &mut BasicHistory) -> Result<Option<String>> {
-    let input: String = Input::new()
-        .allow_empty(true)
-        .completion_with(&PathCompletion::default())
-        .history_with(history)
-        .interact_text()?;
+pub const EDIT_COMMAND: &str = "/edit";
+const MULTILINE_DELIMITER: &str = "\"\"\"";
+
+pub fn input_chat_prompt(
+    history: &mut PersistentHistory,
+    completion: &ChatCompletion,
+) -> Result<Option<String>> {
+    loop {
+        let input = input_line(history, completion)?;
+
+        let prompt = match input.trim() {
+            EDIT_COMMAND => match edit_prompt()? {
+                None => continue,
+                Some(prompt) => prompt,
+            },
+            trimmed if trimmed.starts_with(MULTILINE_DELIMITER) => {
+                input_multiline_prompt(history, completion, &input)?
+            }
+            _ => input,
+        };
 
-    match input.trim().is_empty() {
-        true => Ok(None),
-        false => Ok(Some(input)),
+        if prompt.trim().is_empty() {
+            return Ok(None);
+        }
+        history.add(&prompt);
+        return Ok(Some(prompt));
     }
 }
 
+fn input_line(history: &PersistentHistory, completion: &ChatCompletion) -> Result<String> {
+    input_prompt_line(&history.entries(), completion)
+}
+
+/// Reads lines until the closing delimiter, the opening line may already contain text
+fn input_multiline_prompt(
+    history: &PersistentHistory,
+    completion: &ChatCompletion,
+    first_line: &str,
+) -> Result<String> {
+    let mut prompt = MultilinePrompt::open(first_line);
+    while !prompt.push(&input_line(history, completion)?) {}
+
+    Ok(prompt.text())
+}
+
+/// Prompt wrapped in `"""`, only a line holding the delimiter alone closes it,
+/// so pasted text with docstrings stays whole
+struct MultilinePrompt {
+    lines: Vec<String>,
+}
+
+impl MultilinePrompt {
+    fn open(first_line: &str) -> Self {
+        let first_line = first_line.trim_start();
+        let first_line = first_line
+            .strip_prefix(MULTILINE_DELIMITER)
+            .unwrap_or(first_line);
+
+        Self {
+            lines: vec![first_line.to_owned()],
+        }
+    }
+
+    /// Returns `true` once the closing delimiter was pushed
+    fn push(&mut self, line: &str) -> bool {
+        if line.trim() == MULTILINE_DELIMITER {
+            return true;
+        }
+        self.lines.push(line.to_owned());
+        false
+    }
+
+    fn text(&self) -> String {
+        self.lines.join("\n").trim().to_owned()
+    }
+}
+
+fn edit_prompt() -> Result<Option<String>> {
+    let prompt = Editor::new().extension(".md").edit("")?;
+
+    Ok(prompt.filter(|prompt| !prompt.trim().is_empty()))
+}
+
 pub fn input_api_key() -> Result<String> {
     let input: String = Input::new()
         .with_prompt("Please input OpenAi API key")
-        .validate_with(openai_api_key_format_validator)
+        .validate_with(|input: &String| openai_api_key_format_validator(input))
         .interact_text()?;
 
     Ok(input)
@@ -61,3 +134,130 @@ pub fn confirm_execute() -> Result<bool> {
 
     Ok(confirmation)
 }
+
+/// Confirmation for risky actions, requires typing the expected word instead of a single key press
+pub fn confirm_typed(prompt: &str, expected: &str) -> Result<bool> {
+    let input: String = Input::new()
+        .with_prompt(format!("{prompt} Type \"{expected}\" to confirm"))
+        .allow_empty(true)
+        .interact_text()?;
+
+    Ok(input.trim() == expected)
+}
+
+pub fn confirm(prompt: &str) -> Result<bool> {
+    let confirmation = Confirm::new()
+        .with_prompt(prompt)
+        .default(false)
+        .interact()?;
+
+    Ok(confirmation)
+}
+
+/// Returns `None` when user wants to continue the chat
+pub fn select_code_block(blocks: &[CodeBlock]) -> Result<Option<&CodeBlock>> {
+    let mut items = vec!["Continue".to_owned()];
+    items.extend(blocks.iter().enumerate().map(|(index, block)| {
+        let first_line = block.code.lines().next().unwrap_or_default();
+        let language = block.language.as_deref().unwrap_or("text");
+        format!("#{} {language}: {first_line}", index + 1)
+    }));
+
+    let selected = Select::new()
+        .with_prompt("Code blocks")
+        .items(&items)
+        .default(0)
+        .interact_opt()?;
+
+    match selected {
+        None | Some(0) => Ok(None),
+        Some(index) => Ok(blocks.get(index - 1)),
+    }
+}
+
+pub fn select_code_action(block: &CodeBlock) -> Result<Option<CodeAction>> {
+    let actions = CodeAction::available(block);
+    let labels: Vec<_> = actions.iter().map(|action| action.label()).collect();
+
+    let selected = Select::new()
+        .with_prompt("What do you want to do with the code?")
+        .items(&labels)
+        .default(0)
+        .interact_opt()?;
+
+    Ok(selected.map(|index| actions[index]))
+}
+
+#[derive(Clone, Copy)]
+pub enum CommitAction {
+    Commit,
+    Edit,
+    Regenerate,
+    Cancel,
+}
+
+pub fn select_commit_action() -> Result<CommitAction> {
+    let actions = [
+        (CommitAction::Commit, "Commit"),
+        (CommitAction::Edit, "Edit message"),
+        (CommitAction::Regenerate, "Regenerate message"),
+        (CommitAction::Cancel, "Cancel"),
+    ];
+    let labels: Vec<_> = actions.iter().map(|(_, label)| *label).collect();
+
+    let selected = Select::new()
+        .with_prompt("What do you want to do with the message?")
+        .items(&labels)
+        .default(0)
+        .interact_opt()?;
+
+    Ok(selected.map_or(CommitAction::Cancel, |index| actions[index].0))
+}
+
+/// Opens text in `$EDITOR`, returns `None` when it was not saved or left empty
+pub fn edit_text(text: &str) -> Result<Option<String>> {
+    let edited = Editor::new().extension(".txt").edit(text)?;
+
+    Ok(edited
+        .map(|edited| edited.trim().to_owned())
+        .filter(|edited| !edited.is_empty()))
+}
+
+pub fn input_file_path() -> Result<String> {
+    let input: String = Input::new()
+        .with_prompt("File path")
+        .completion_with(&ChatCompletion::default())
+        .interact_text()?;
+
+    Ok(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multiline_prompt() {
+        let mut prompt = MultilinePrompt::open("\"\"\"review this:");
+        assert!(!prompt.push("def f():"));
+        assert!(!prompt.push("    \"\"\"Docstring.\"\"\""));
+        assert!(!prompt.push("    return 1 \"\"\""));
+        assert!(prompt.push(" \"\"\" "));
+
+        assert_eq!(
+            prompt.text(),
+            "review this:\ndef f():\n    \"\"\"Docstring.\"\"\"\n    return 1 \"\"\""
+        );
+    }
+
+    #[test]
+    fn test_multiline_prompt_opened_alone() {
+        let mut prompt = MultilinePrompt::open("\"\"\"");
+        assert!(!prompt.push("first"));
+        assert!(!prompt.push(""));
+        assert!(!prompt.push("second"));
+        assert!(prompt.push("\"\"\""));
+
+        assert_eq!(prompt.text(), "first\n\nsecond");
+    }
+}
@@ -0,0 +1,46 @@
+use console::Style;
+use lib::FileEdit;
+use similar::{ChangeTag, TextDiff};
+
+const CONTEXT_RADIUS: usize = 3;
+
+pub(crate) fn print_file_diff(edit: &FileEdit) {
+    let original = edit.original.as_deref().unwrap_or_default();
+    let edited = edit.edited.as_deref().unwrap_or_default();
+    let header = match (&edit.original, &edit.edited) {
+        (None, _) => format!("{} (new file)", edit.path),
+        (_, None) => format!("{} (deleted)", edit.path),
+        _ => edit.path.to_owned(),
+    };
+    println!("{}", Style::new().bold().apply_to(header));
+
+    let diff = TextDiff::from_lines(original, edited);
+    for group in diff.grouped_ops(CONTEXT_RADIUS) {
+        let (first, last) = (group.first(), group.last());
+        if let (Some(first), Some(last)) = (first, last) {
+            let old_range = first.old_range().start + 1..last.old_range().end + 1;
+            let new_range = first.new_range().start + 1..last.new_range().end + 1;
+            let hunk_header = format!(
+                "@@ -{},{} +{},{} @@",
+                old_range.start,
+                old_range.len(),
+                new_range.start,
+                new_range.len()
+            );
+            println!("{}", Style::new().cyan().apply_to(hunk_header));
+        }
+
+        for change in group.iter().flat_map(|op| diff.iter_changes(op)) {
+            let (sign, style) = match change.tag() {
+                ChangeTag::Delete => ("-", Style::new().red()),
+                ChangeTag::Insert => ("+", Style::new().green()),
+                ChangeTag::Equal => (" ", Style::new().dim()),
+            };
+            let line = change.to_string_lossy();
+            print!("{}", style.apply_to(format!("{sign}{line}")));
+            if change.missing_newline() {
+                println!();
+            }
+        }
+    }
+}
@@ -0,0 +1,39 @@
+use crate::dialog::EDIT_COMMAND;
+use crate::storage::Storage;
+use lib::PromptHistory;
+
+pub(crate) struct PersistentHistory {
+    command: String,
+    history: PromptHistory,
+}
+
+impl PersistentHistory {
+    pub(crate) fn load(command: &str, max_entries: usize) -> anyhow::Result<Self> {
+        let history = Storage::prompt_history()?.load(command, max_entries)?;
+
+        Ok(Self {
+            command: command.to_owned(),
+            history,
+        })
+    }
+
+    pub(crate) fn entries(&self) -> Vec<&str> {
+        self.history.recent_first()
+    }
+
+    /// Saves the whole prompt as one entry, failure is only reported
+    pub(crate) fn add(&mut self, prompt: &str) {
+        if prompt.trim() == EDIT_COMMAND {
+            return;
+        }
+
+        self.history.push(prompt);
+        if let Err(err) = self.save() {
+            eprintln!("\n{err:?}");
+        }
+    }
+
+    fn save(&self) -> anyhow::Result<()> {
+        Storage::prompt_history()?.save(&self.command, &self.history)
+    }
+}
@@ -1,7 +1,12 @@
 mod chat_controller;
 mod cmd;
+mod code_actions;
 mod completion;
 mod dialog;
+mod diff;
+mod history;
+mod markdown;
+mod prompt_input;
 mod storage;
 
 use crate::cmd::{match_cmd, Command};
@@ -0,0 +1,466 @@
+use console::{measure_text_width, Style};
+use pulldown_cmark::{CodeBlockKind, Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use std::sync::OnceLock;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::parsing::SyntaxSet;
+use syntect::util::{as_24_bit_terminal_escaped, LinesWithEndings};
+
+const CODE_THEME: &str = "base16-ocean.dark";
+const MAX_WIDTH: usize = 120;
+
+/// Renders markdown to text styled for the terminal, paragraphs are wrapped to the terminal width
+pub(crate) fn render_markdown(markdown: &str) -> String {
+    let (_, columns) = console::Term::stdout().size();
+    render_markdown_with_width(markdown, usize::from(columns).clamp(20, MAX_WIDTH))
+}
+
+fn render_markdown_with_width(markdown: &str, width: usize) -> String {
+    let mut renderer = MarkdownRenderer::new(width);
+    for event in Parser::new_ext(
+        markdown,
+        Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS,
+    ) {
+        renderer.render(event);
+    }
+
+    renderer.out.trim_end().to_owned()
+}
+
+#[derive(Default)]
+struct CodeBlock {
+    language: String,
+    code: String,
+}
+
+#[derive(Default)]
+struct Table {
+    rows: Vec<Vec<String>>,
+    header_rows: usize,
+}
+
+struct MarkdownRenderer {
+    width: usize,
+    out: String,
+    text: String,
+    bold: bool,
+    italic: bool,
+    strikethrough: bool,
+    heading: Option<HeadingLevel>,
+    quote_depth: usize,
+    lists: Vec<Option<u64>>,
+    item_marker: Option<String>,
+    link_url: Option<String>,
+    /// Unstyled text of the current link, to leave out the URL of autolinks
+    link_text: String,
+    code_block: Option<CodeBlock>,
+    table: Option<Table>,
+}
+
+impl MarkdownRenderer {
+    fn new(width: usize) -> Self {
+        Self {
+            width,
+            out: String::new(),
+            text: String::new(),
+            bold: false,
+            italic: false,
+            strikethrough: false,
+            heading: None,
+            quote_depth: 0,
+            lists: vec![],
+            item_marker: None,
+            link_url: None,
+            link_text: String::new(),
+            code_block: None,
+            table: None,
+        }
+    }
+
+    fn render(&mut self, event: Event) {
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(text) => match &mut self.code_block {
+                Some(code_block) => code_block.code.push_str(&text),
+                None => self.push_text(&text),
+            },
+            Event::Code(code) => {
+                let code = Style::new().yellow().apply_to(code.as_ref()).to_string();
+                self.push_styled(&code);
+            }
+            Event::InlineMath(math) | Event::DisplayMath(math) => self.push_text(&math),
+            Event::Html(html) | Event::InlineHtml(html) => self.push_text(&html),
+            Event::SoftBreak => self.push_text(" "),
+            Event::HardBreak => self.push_styled("\n"),
+            Event::Rule => {
+                self.flush();
+                let rule = "─".repeat(self.width.saturating_sub(self.prefix_width()));
+                self.push_line(&Style::new().dim().apply_to(rule).to_string());
+                self.out.push('\n');
+            }
+            Event::TaskListMarker(checked) => {
+                self.push_styled(if checked { "[x] " } else { "[ ] " })
+            }
+            Event::FootnoteReference(name) => self.push_text(&format!("[^{name}]")),
+        }
+    }
+
+    fn start(&mut self, tag: Tag) {
+        match tag {
+            Tag::Paragraph => self.flush(),
+            Tag::Heading { level, .. } => {
+                self.flush();
+                self.heading = Some(level);
+            }
+            Tag::BlockQuote(_) => {
+                self.flush();
+                self.quote_depth += 1;
+            }
+            Tag::CodeBlock(kind) => {
+                self.flush();
+                self.flush_marker();
+                let language = match kind {
+                    CodeBlockKind::Fenced(info) => info
+                        .split_whitespace()
+                        .next()
+                        .unwrap_or_default()
+                        .to_owned(),
+                    CodeBlockKind::Indented => String::new(),
+                };
+                self.code_block = Some(CodeBlock {
+                    language,
+                    code: String::new(),
+                });
+            }
+            Tag::List(start) => {
+                self.flush();
+                self.flush_marker();
+                self.lists.push(start);
+            }
+            Tag::Item => {
+                self.flush();
+                let marker = match self.lists.last_mut() {
+                    Some(Some(number)) => {
+                        *number += 1;
+                        format!("{}. ", *number - 1)
+                    }
+                    _ => "• ".to_owned(),
+                };
+                self.item_marker = Some(marker);
+            }
+            Tag::Table(_) => {
+                self.flush();
+                self.flush_marker();
+                self.table = Some(Table::default());
+            }
+            Tag::TableHead | Tag::TableRow => {
+                if let Some(table) = &mut self.table {
+                    table.rows.push(vec![]);
+                }
+            }
+            Tag::TableCell => self.text.clear(),
+            Tag::Emphasis => self.italic = true,
+            Tag::Strong => self.bold = true,
+            Tag::Strikethrough => self.strikethrough = true,
+            Tag::Link { dest_url, .. } => {
+                self.link_url = Some(dest_url.to_string());
+                self.link_text.clear();
+            }
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::Paragraph => {
+                self.flush();
+                if self.lists.is_empty() {
+                    self.blank_line();
+                }
+            }
+            TagEnd::Heading(_) => {
+                self.flush();
+                self.heading = None;
+                self.blank_line();
+            }
+            TagEnd::BlockQuote(_) => {
+                self.flush();
+                self.quote_depth -= 1;
+            }
+            TagEnd::CodeBlock => {
+                if let Some(code_block) = self.code_block.take() {
+                    self.render_code_block(code_block);
+                }
+                self.blank_line();
+            }
+            TagEnd::List(_) => {
+                self.flush();
+                self.lists.pop();
+                if self.lists.is_empty() {
+                    self.blank_line();
+                }
+            }
+            TagEnd::Item => {
+                self.flush();
+                self.flush_marker();
+            }
+            TagEnd::TableHead => {
+                if let Some(table) = &mut self.table {
+                    table.header_rows = table.rows.len();
+                }
+            }
+            TagEnd::TableCell => {
+                let cell = std::mem::take(&mut self.text);
+                if let Some(row) = self.table.as_mut().and_then(|t| t.rows.last_mut()) {
+                    row.push(cell.trim().to_owned());
+                }
+            }
+            TagEnd::Table => {
+                if let Some(table) = self.table.take() {
+                    self.render_table(table);
+                }
+                self.blank_line();
+            }
+            TagEnd::Emphasis => self.italic = false,
+            TagEnd::Strong => self.bold = false,
+            TagEnd::Strikethrough => self.strikethrough = false,
+            TagEnd::Link => {
+                if let Some(url) = self.link_url.take() {
+                    if url.strip_prefix("mailto:").unwrap_or(&url) != self.link_text {
+                        let url = Style::new().dim().apply_to(format!(" ({url})")).to_string();
+                        self.push_styled(&url);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn push_text(&mut self, text: &str) {
+        let mut style = Style::new();
+        if self.bold || self.heading.is_some() {
+            style = style.bold();
+        }
+        if self.heading == Some(HeadingLevel::H1) {
+            style = style.underlined();
+        }
+        if self.heading.is_some() {
+            style = style.cyan();
+        }
+        if self.italic {
+            style = style.italic();
+        }
+        if self.strikethrough {
+            style = style.strikethrough();
+        }
+        if self.link_url.is_some() {
+            style = style.blue().underlined();
+            self.link_text.push_str(text);
+        }
+
+        let text = style.apply_to(text).to_string();
+        self.push_styled(&text);
+    }
+
+    fn push_styled(&mut self, text: &str) {
+        self.text.push_str(text);
+    }
+
+    fn prefix(&self) -> String {
+        let quote = Style::new().dim().apply_to("│ ").to_string();
+        let list_indent = "  ".repeat(self.lists.len().saturating_sub(1));
+        format!("{}{list_indent}", quote.repeat(self.quote_depth))
+    }
+
+    fn prefix_width(&self) -> usize {
+        measure_text_width(&self.prefix())
+    }
+
+    /// Writes the collected inline text as a wrapped block, list item marker waits for the first text
+    /// so paragraphs of loose lists start on the marker line
+    fn flush(&mut self) {
+        if self.table.is_some() {
+            return;
+        }
+        let text = std::mem::take(&mut self.text);
+        if text.trim().is_empty() {
+            return;
+        }
+        let marker = self.item_marker.take();
+
+        let prefix = self.prefix();
+        let marker = marker.unwrap_or_default();
+        let initial_indent = format!("{prefix}{marker}");
+        let subsequent_indent = format!("{prefix}{}", " ".repeat(measure_text_width(&marker)));
+        let options = textwrap::Options::new(self.width)
+            .initial_indent(&initial_indent)
+            .subsequent_indent(&subsequent_indent);
+
+        self.out.push_str(&textwrap::fill(text.trim(), options));
+        self.out.push('\n');
+    }
+
+    /// Writes marker of list item without text of its own, like one starting with a code block
+    fn flush_marker(&mut self) {
+        if let Some(marker) = self.item_marker.take() {
+            let prefix = self.prefix();
+            self.out
+                .push_str(&format!("{prefix}{}\n", marker.trim_end()));
+        }
+    }
+
+    fn push_line(&mut self, line: &str) {
+        let prefix = self.prefix();
+        self.out.push_str(&format!("{prefix}{line}\n"));
+    }
+
+    fn blank_line(&mut self) {
+        if !self.out.is_empty() && !self.out.ends_with("\n\n") {
+            self.out.push('\n');
+        }
+    }
+
+    fn render_code_block(&mut self, code_block: CodeBlock) {
+        let syntaxes = syntax_set();
+        let syntax = syntaxes
+            .find_syntax_by_token(&code_block.language)
+            .unwrap_or_else(|| syntaxes.find_syntax_plain_text());
+        let mut highlighter = HighlightLines::new(syntax, code_theme());
+
+        for line in LinesWithEndings::from(&code_block.code) {
+            let highlighted = match highlighter.highlight_line(line, syntaxes) {
+                Ok(ranges) => as_24_bit_terminal_escaped(&ranges, false),
+                Err(_) => line.to_owned(),
+            };
+            self.push_line(&format!(
+                "    {}\x1b[0m",
+                highlighted.trim_end_matches('\n')
+            ));
+        }
+    }
+
+    fn render_table(&mut self, table: Table) {
+        let columns = table
+            .rows
+            .iter()
+            .map(|row| row.len())
+            .max()
+            .unwrap_or_default();
+        let widths: Vec<usize> = (0..columns)
+            .map(|column| {
+                table
+                    .rows
+                    .iter()
+                    .filter_map(|row| row.get(column))
+                    .map(|cell| measure_text_width(cell))
+                    .max()
+                    .unwrap_or_default()
+            })
+            .collect();
+
+        let separator = Style::new().dim().apply_to(" │ ").to_string();
+        for (index, row) in table.rows.iter().enumerate() {
+            let cells: Vec<String> = widths
+                .iter()
+                .enumerate()
+                .map(|(column, width)| {
+                    let cell = row.get(column).map(|c| c.as_str()).unwrap_or_default();
+                    let padding = " ".repeat(width - measure_text_width(cell));
+                    match index < table.header_rows {
+                        true => format!("{}{padding}", Style::new().bold().apply_to(cell)),
+                        false => format!("{cell}{padding}"),
+                    }
+                })
+                .collect();
+            self.push_line(cells.join(&separator).trim_end());
+
+            if index + 1 == table.header_rows {
+                let rule = widths
+                    .iter()
+                    .map(|width| "─".repeat(*width))
+                    .collect::<Vec<_>>()
+                    .join("─┼─");
+                self.push_line(&Style::new().dim().apply_to(rule).to_string());
+            }
+        }
+    }
+}
+
+fn syntax_set() -> &'static SyntaxSet {
+    static SYNTAX_SET: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAX_SET.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+fn code_theme() -> &'static Theme {
+    static THEME: OnceLock<Theme> = OnceLock::new();
+    THEME.get_or_init(|| {
+        let mut themes = ThemeSet::load_defaults().themes;
+        themes.remove(CODE_THEME).unwrap_or_default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(markdown: &str) -> String {
+        console::strip_ansi_codes(&render_markdown_with_width(markdown, 40)).to_string()
+    }
+
+    #[test]
+    fn test_render_heading() {
+        assert_eq!(render("# Title\n\nSome text"), "Title\n\nSome text");
+    }
+
+    #[test]
+    fn test_render_tight_list() {
+        assert_eq!(render("- first\n- second"), "• first\n• second");
+    }
+
+    #[test]
+    fn test_render_loose_list() {
+        assert_eq!(render("1. first\n\n2. second"), "1. first\n2. second");
+    }
+
+    #[test]
+    fn test_render_nested_list() {
+        assert_eq!(
+            render("- outer\n  - inner\n- next"),
+            "• outer\n  • inner\n• next"
+        );
+    }
+
+    #[test]
+    fn test_render_wrapped_list_item() {
+        assert_eq!(
+            render("- a long list item wrapped under its own text"),
+            "• a long list item wrapped under its\n  own text"
+        );
+    }
+
+    #[test]
+    fn test_render_code_block() {
+        assert_eq!(render("Run:\n\n```sh\nls -la\n```"), "Run:\n\n    ls -la");
+        assert_eq!(render("- ```sh\n  ls\n  ```"), "•\n    ls");
+    }
+
+    #[test]
+    fn test_render_link() {
+        assert_eq!(
+            render("See [docs](https://docs.rs)"),
+            "See docs (https://docs.rs)"
+        );
+        assert_eq!(render("<https://docs.rs>"), "https://docs.rs");
+        assert_eq!(render("<docs@rust-lang.org>"), "docs@rust-lang.org");
+    }
+
+    #[test]
+    fn test_render_autolink_with_colors() {
+        console::set_colors_enabled(true);
+        let rendered = render_markdown_with_width("<https://docs.rs>", 40);
+
+        assert_ne!(console::strip_ansi_codes(&rendered), rendered);
+        assert_eq!(console::strip_ansi_codes(&rendered), "https://docs.rs");
+    }
+}
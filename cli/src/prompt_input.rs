@@ -0,0 +1,318 @@
+use crate::completion::ChatCompletion;
+use anyhow::Result;
+use console::{measure_text_width, Key, Term};
+use dialoguer::Completion;
+use std::io;
+
+const PROMPT: &str = ": ";
+/// Ctrl-R comes as a plain control character, dialoguer `Input` drops those
+const REVERSE_SEARCH_KEY: char = '\x12';
+/// Ctrl-G cancels the search like in readline
+const CANCEL_SEARCH_KEY: char = '\x07';
+/// Shown in place of line breaks of recalled multi-line prompts, which stay in the submitted text
+const LINE_BREAK_SYMBOL: char = '↵';
+
+/// Single line prompt with completion on tab or right arrow, history on up and down arrows
+/// and reverse incremental search of history on Ctrl-R, rendered on stderr.
+/// `history` holds prompts from the most recent one
+pub(crate) fn input_prompt_line(history: &[&str], completion: &ChatCompletion) -> Result<String> {
+    let term = Term::stderr();
+    if !term.is_term() {
+        return Err(io::Error::new(io::ErrorKind::NotConnected, "not a terminal").into());
+    }
+
+    let mut line = Line::new("");
+    let mut history_position: Option<usize> = None;
+    let mut search: Option<ReverseSearch> = None;
+    let mut renderer = Renderer::new(&term);
+
+    loop {
+        match &search {
+            Some(search) => {
+                let matched = search.matched(history).unwrap_or_default();
+                renderer.render(&search.prompt(), matched, matched.chars().count())?
+            }
+            None => renderer.render(PROMPT, &line.text(), line.position)?,
+        }
+
+        let key = match term.read_key() {
+            Ok(key) => key,
+            Err(err) => {
+                term.write_line("")?;
+                return Err(err.into());
+            }
+        };
+
+        if let Some(active_search) = &mut search {
+            match key {
+                Key::Char(REVERSE_SEARCH_KEY) => active_search.next(history),
+                Key::Char(c) if !c.is_control() => active_search.push(c, history),
+                Key::Backspace => active_search.pop(history),
+                Key::Escape | Key::Char(CANCEL_SEARCH_KEY) => {
+                    line = Line::new(&active_search.original);
+                    search = None;
+                }
+                Key::Enter => {
+                    line = Line::new(active_search.accepted(history));
+                    break;
+                }
+                _ => {
+                    line = Line::new(active_search.accepted(history));
+                    search = None;
+                }
+            }
+            continue;
+        }
+
+        match key {
+            Key::Enter => break,
+            Key::Char(REVERSE_SEARCH_KEY) => search = Some(ReverseSearch::new(&line.text())),
+            Key::Char(c) if !c.is_control() => line.insert(c),
+            Key::Backspace => line.backspace(),
+            Key::Del => line.delete(),
+            Key::ArrowLeft => line.position = line.position.saturating_sub(1),
+            Key::ArrowRight if line.position < line.chars.len() => line.position += 1,
+            Key::Home => line.position = 0,
+            Key::End => line.position = line.chars.len(),
+            Key::ArrowRight | Key::Tab => {
+                if let Some(completed) = completion.get(&line.text()) {
+                    line = Line::new(&completed);
+                }
+            }
+            Key::ArrowUp => {
+                let position = history_position.map_or(0, |position| position + 1);
+                if let Some(entry) = history.get(position) {
+                    history_position = Some(position);
+                    line = Line::new(entry);
+                }
+            }
+            Key::ArrowDown => match history_position {
+                None => {}
+                Some(0) => {
+                    history_position = None;
+                    line = Line::new("");
+                }
+                Some(position) => {
+                    history_position = Some(position - 1);
+                    line = Line::new(history[position - 1]);
+                }
+            },
+            _ => {}
+        }
+    }
+
+    let text = line.text();
+    renderer.render(PROMPT, &text, line.chars.len())?;
+    term.write_line("")?;
+
+    Ok(text)
+}
+
+/// Edited text with cursor position counted in characters
+struct Line {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Line {
+    fn new(text: &str) -> Self {
+        let chars: Vec<char> = text.chars().collect();
+        Self {
+            position: chars.len(),
+            chars,
+        }
+    }
+
+    fn text(&self) -> String {
+        self.chars.iter().collect()
+    }
+
+    fn insert(&mut self, c: char) {
+        self.chars.insert(self.position, c);
+        self.position += 1;
+    }
+
+    fn backspace(&mut self) {
+        if self.position > 0 {
+            self.position -= 1;
+            self.chars.remove(self.position);
+        }
+    }
+
+    fn delete(&mut self) {
+        if self.position < self.chars.len() {
+            self.chars.remove(self.position);
+        }
+    }
+}
+
+/// Reverse incremental search through history ordered from the most recent prompt,
+/// repeated Ctrl-R moves to older matches
+struct ReverseSearch {
+    query: String,
+    index: Option<usize>,
+    failed: bool,
+    original: String,
+}
+
+impl ReverseSearch {
+    fn new(original: &str) -> Self {
+        Self {
+            query: String::new(),
+            index: None,
+            failed: false,
+            original: original.to_owned(),
+        }
+    }
+
+    fn push(&mut self, c: char, history: &[&str]) {
+        self.query.push(c);
+        self.find(history, self.index.unwrap_or_default());
+    }
+
+    fn pop(&mut self, history: &[&str]) {
+        self.query.pop();
+        self.index = None;
+        self.find(history, 0);
+    }
+
+    fn next(&mut self, history: &[&str]) {
+        match self.index {
+            Some(index) => self.find(history, index + 1),
+            None => self.find(history, 0),
+        }
+    }
+
+    /// Moves to the first match from `from`, keeps the previous match when there is none
+    fn find(&mut self, history: &[&str], from: usize) {
+        if self.query.is_empty() {
+            self.failed = false;
+            return;
+        }
+
+        let query = self.query.to_lowercase();
+        let found = history
+            .iter()
+            .enumerate()
+            .skip(from)
+            .find(|(_, entry)| entry.to_lowercase().contains(&query))
+            .map(|(index, _)| index);
+
+        self.failed = found.is_none();
+        if found.is_some() {
+            self.index = found;
+        }
+    }
+
+    fn matched<'h>(&self, history: &[&'h str]) -> Option<&'h str> {
+        self.index.and_then(|index| history.get(index).copied())
+    }
+
+    /// Matched prompt, or the text from before the search when nothing matched
+    fn accepted<'s>(&'s self, history: &[&'s str]) -> &'s str {
+        self.matched(history).unwrap_or(&self.original)
+    }
+
+    fn prompt(&self) -> String {
+        let failed = if self.failed { "failed " } else { "" };
+        format!("({failed}reverse-i-search)'{}': ", self.query)
+    }
+}
+
+/// Redraws prompt and text in place, also when the text wraps over several rows
+struct Renderer<'t> {
+    term: &'t Term,
+    cursor_row: usize,
+}
+
+impl<'t> Renderer<'t> {
+    fn new(term: &'t Term) -> Self {
+        Self {
+            term,
+            cursor_row: 0,
+        }
+    }
+
+    fn render(&mut self, prompt: &str, text: &str, cursor: usize) -> io::Result<()> {
+        let columns = usize::from(self.term.size().1).max(1);
+        let text = displayed(text);
+        let line = format!("{prompt}{text}");
+
+        self.term.move_cursor_up(self.cursor_row)?;
+        self.term.write_str("\r")?;
+        self.term.clear_to_end_of_screen()?;
+        self.term.write_str(&line)?;
+
+        // terminal keeps cursor on the last column of a full row, move it to the next one
+        let width = measure_text_width(&line);
+        if width > 0 && width.is_multiple_of(columns) {
+            self.term.write_str("\n")?;
+        }
+
+        let before_cursor: String = text.chars().take(cursor).collect();
+        let cursor_width = measure_text_width(prompt) + measure_text_width(&before_cursor);
+        let row = cursor_width / columns;
+        self.term.write_str("\r")?;
+        self.term.move_cursor_up(width / columns - row)?;
+        self.term.move_cursor_right(cursor_width % columns)?;
+        self.cursor_row = row;
+
+        self.term.flush()
+    }
+}
+
+fn displayed(text: &str) -> String {
+    text.replace('\n', &LINE_BREAK_SYMBOL.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HISTORY: &[&str] = &["git status", "explain {main.rs}", "git log"];
+
+    #[test]
+    fn test_line_editing() {
+        let mut line = Line::new("helo");
+        line.position = 3;
+        line.insert('l');
+        line.backspace();
+        line.insert('l');
+        assert_eq!(line.text(), "hello");
+
+        line.position = 0;
+        line.delete();
+        line.backspace();
+        assert_eq!(line.text(), "ello");
+    }
+
+    #[test]
+    fn test_reverse_search() {
+        let mut search = ReverseSearch::new("draft");
+        search.push('g', HISTORY);
+        search.push('I', HISTORY);
+        assert_eq!(search.matched(HISTORY), Some("git status"));
+
+        search.next(HISTORY);
+        assert_eq!(search.matched(HISTORY), Some("git log"));
+
+        search.next(HISTORY);
+        assert!(search.failed);
+        assert_eq!(search.matched(HISTORY), Some("git log"));
+        assert_eq!(search.prompt(), "(failed reverse-i-search)'gI': ");
+
+        search.pop(HISTORY);
+        search.pop(HISTORY);
+        search.push('x', HISTORY);
+        assert_eq!(search.accepted(HISTORY), "explain {main.rs}");
+    }
+
+    #[test]
+    fn test_reverse_search_without_match() {
+        let mut search = ReverseSearch::new("draft");
+        search.push('z', HISTORY);
+
+        assert!(search.failed);
+        assert_eq!(search.accepted(HISTORY), "draft");
+    }
+}
@@ -1,35 +1,158 @@
 use anyhow::Context;
-use lib::{ChatRecordStorage, ExpliceConfigStorage, LocalAssistants, APP_NAME};
-use persist::LocalJsonStorage;
+use lib::{
+    ChatRecordStorage, ExpliceConfig, ExpliceConfigStorage, LocalAssistants, PromptHistoryStorage,
+    StorageBackend, UsageStorage, APP_NAME,
+};
+use persist::{
+    BackendStorage, CompactionStats, LocalJsonStorage, LocalJsonlStorage, SqliteStorage,
+    SqliteTable,
+};
+use serde_json::Value;
 use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 const CONFIG_FILE_NAME: &str = "config.json";
 const ASSISTANTS_FILE_NAME: &str = "assistants.json";
 const CHAT_RECORDS_FILE_NAME: &str = "chat_records.json";
+const CHAT_RECORDS_JSONL_FILE_NAME: &str = "chat_records.jsonl";
+const PROMPT_HISTORY_FILE_NAME: &str = "prompt_history.json";
+const USAGE_FILE_NAME: &str = "usage.json";
+const DATABASE_FILE_NAME: &str = "explice.db";
+const PROMPT_HISTORY_NAMESPACE: &str = "prompt_history";
+const USAGE_NAMESPACE: &str = "usage";
+const EDIT_BACKUPS_DIR_NAME: &str = "edit_backups";
 
 pub(crate) struct Storage;
 
 impl Storage {
     pub(crate) fn config() -> anyhow::Result<ExpliceConfigStorage<LocalJsonStorage>> {
-        let storage = LocalJsonStorage::new(user_config_path(CONFIG_FILE_NAME)?);
+        // config may hold API key
+        let storage =
+            LocalJsonStorage::new(user_config_path(CONFIG_FILE_NAME)?).with_private_permissions();
         let config_storage = ExpliceConfigStorage::new(storage);
 
         Ok(config_storage)
     }
 
-    pub(crate) fn assistants() -> anyhow::Result<LocalAssistants<LocalJsonStorage>> {
-        let storage = LocalJsonStorage::new(user_config_path(ASSISTANTS_FILE_NAME)?);
+    pub(crate) fn assistants() -> anyhow::Result<LocalAssistants<BackendStorage>> {
+        let storage = backend_storage(ASSISTANTS_FILE_NAME, SqliteTable::Assistants)?;
         let local_assistants = LocalAssistants::new(storage);
 
         Ok(local_assistants)
     }
 
-    pub(crate) fn chat_records() -> anyhow::Result<ChatRecordStorage<LocalJsonStorage>> {
-        let storage = LocalJsonStorage::new(user_config_path(CHAT_RECORDS_FILE_NAME)?);
+    /// Chat records of JSON backend are appended to JSONL file instead of rewriting whole history
+    pub(crate) fn chat_records() -> anyhow::Result<ChatRecordStorage<BackendStorage>> {
+        let storage = match storage_backend()? {
+            StorageBackend::Json => BackendStorage::Jsonl(chat_records_jsonl()?),
+            StorageBackend::Sqlite => {
+                backend_storage(CHAT_RECORDS_FILE_NAME, SqliteTable::ChatRecords)?
+            }
+        };
         let chat_records = ChatRecordStorage::new(storage);
 
         Ok(chat_records)
     }
+
+    pub(crate) fn prompt_history() -> anyhow::Result<PromptHistoryStorage<BackendStorage>> {
+        let storage = backend_storage(
+            PROMPT_HISTORY_FILE_NAME,
+            SqliteTable::KeyValues(PROMPT_HISTORY_NAMESPACE.to_owned()),
+        )?;
+        let prompt_history = PromptHistoryStorage::new(storage);
+
+        Ok(prompt_history)
+    }
+
+    pub(crate) fn usage(config: &ExpliceConfig) -> anyhow::Result<UsageStorage<BackendStorage>> {
+        let storage = backend_storage(
+            USAGE_FILE_NAME,
+            SqliteTable::KeyValues(USAGE_NAMESPACE.to_owned()),
+        )?;
+        let usage = UsageStorage::new(storage).with_budgets(*config.budgets());
+
+        Ok(usage)
+    }
+
+    /// Copies entries from JSON files missing in the database, returns imported count per file
+    pub(crate) fn migrate_to_sqlite() -> anyhow::Result<Vec<(&'static str, usize)>> {
+        let tables = [
+            (ASSISTANTS_FILE_NAME, SqliteTable::Assistants),
+            (CHAT_RECORDS_FILE_NAME, SqliteTable::ChatRecords),
+            (
+                PROMPT_HISTORY_FILE_NAME,
+                SqliteTable::KeyValues(PROMPT_HISTORY_NAMESPACE.to_owned()),
+            ),
+            (
+                USAGE_FILE_NAME,
+                SqliteTable::KeyValues(USAGE_NAMESPACE.to_owned()),
+            ),
+        ];
+
+        let mut imported = vec![];
+        for (file_name, table) in tables {
+            let entries = match table {
+                SqliteTable::ChatRecords => chat_records_jsonl()?.entries::<Value>(),
+                _ => LocalJsonStorage::new(user_config_path(file_name)?).entries::<Value>(),
+            }
+            .with_context(|| format!("failed to read {file_name}"))?;
+            let sqlite = SqliteStorage::open(user_config_path(DATABASE_FILE_NAME)?, table)?;
+            imported.push((file_name, sqlite.import(entries)?));
+        }
+
+        Ok(imported)
+    }
+
+    /// Drops updated and deleted chat records from JSONL file
+    pub(crate) fn compact_chat_records() -> anyhow::Result<CompactionStats> {
+        chat_records_jsonl()?.compact()
+    }
+
+    /// New directory for backups of files changed by a single edit
+    pub(crate) fn edit_backup_dir() -> anyhow::Result<PathBuf> {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+        user_config_path(EDIT_BACKUPS_DIR_NAME).map(|dir| dir.join(timestamp.to_string()))
+    }
+}
+
+fn backend_storage(file_name: &str, table: SqliteTable) -> anyhow::Result<BackendStorage> {
+    let storage = match storage_backend()? {
+        StorageBackend::Json => {
+            BackendStorage::Json(LocalJsonStorage::new(user_config_path(file_name)?))
+        }
+        StorageBackend::Sqlite => BackendStorage::Sqlite(SqliteStorage::open(
+            user_config_path(DATABASE_FILE_NAME)?,
+            table,
+        )?),
+    };
+    Ok(storage)
+}
+
+fn storage_backend() -> anyhow::Result<StorageBackend> {
+    // not initialized config falls back to default backend, so `config` command can set it up
+    let backend = Storage::config()?
+        .read()
+        .map(|config| config.storage_backend())
+        .unwrap_or_default();
+    Ok(backend)
+}
+
+/// JSONL chat records, created from records of the former JSON file on first use
+fn chat_records_jsonl() -> anyhow::Result<LocalJsonlStorage> {
+    let storage = LocalJsonlStorage::new(user_config_path(CHAT_RECORDS_JSONL_FILE_NAME)?);
+    if storage.exists()? {
+        return Ok(storage);
+    }
+
+    let legacy = LocalJsonStorage::new(user_config_path(CHAT_RECORDS_FILE_NAME)?);
+    // ULID keys sort by creation time
+    let entries = legacy
+        .entries::<Value>()
+        .with_context(|| format!("failed to read {CHAT_RECORDS_FILE_NAME}"))?;
+    if !entries.is_empty() {
+        storage.import(entries)?;
+    }
+    Ok(storage)
 }
 
 fn user_config_path<P: AsRef<Path>>(file_name: P) -> anyhow::Result<PathBuf> {
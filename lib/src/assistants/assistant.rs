@@ -1,5 +1,6 @@
 use crate::{LocalChatAssistant, OpenAiChatAssistant};
 
+#[derive(Clone)]
 pub enum ChatAssistant {
     LocalAssistant(LocalChatAssistant),
     ExternalAssistant(OpenAiChatAssistant),
@@ -27,6 +28,14 @@ impl ChatAssistant {
         }
     }
 
+    /// Token limit of the assistant, when not set the one from config is used
+    pub fn token_limit(&self) -> Option<u16> {
+        match self {
+            ChatAssistant::LocalAssistant(local_assistant) => local_assistant.token_limit(),
+            ChatAssistant::ExternalAssistant(_) => None,
+        }
+    }
+
     pub fn external(self) -> Option<OpenAiChatAssistant> {
         match self {
             ChatAssistant::LocalAssistant(_) => None,
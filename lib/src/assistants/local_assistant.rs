@@ -6,6 +6,9 @@ pub struct LocalChatAssistant {
     name: String,
     model: String,
     system: String,
+    /// Overrides token limit from config, for assistants that need longer answers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    token_limit: Option<u16>,
 }
 
 impl Default for LocalChatAssistant {
@@ -14,6 +17,7 @@ impl Default for LocalChatAssistant {
             name: "assistant".to_string(),
             model: "gpt-3.5-turbo".to_string(),
             system: "You are a helpful assistant".to_string(),
+            token_limit: None,
         }
     }
 }
@@ -35,6 +39,9 @@ impl LocalChatAssistant {
     pub fn system(&self) -> &str {
         &self.system
     }
+    pub fn token_limit(&self) -> Option<u16> {
+        self.token_limit
+    }
 
     pub fn with_model(mut self, model: &str) -> Self {
         self.model = model.to_owned();
@@ -44,6 +51,10 @@ impl LocalChatAssistant {
         self.system = system.to_owned();
         self
     }
+    pub fn with_token_limit(mut self, token_limit: u16) -> Self {
+        self.token_limit = Some(token_limit);
+        self
+    }
 }
 
 pub struct AssistantData {
@@ -58,6 +69,7 @@ impl From<AssistantData> for LocalChatAssistant {
             name: assistant.name,
             model: assistant.model,
             system: assistant.system,
+            token_limit: None,
         }
     }
 }
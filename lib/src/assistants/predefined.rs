@@ -1,7 +1,49 @@
-use crate::LocalChatAssistant;
+use crate::{LocalChatAssistant, ShellEnvironment};
 
-pub fn shell_assistant(shell: &str) -> LocalChatAssistant {
+pub fn shell_assistant(environment: &ShellEnvironment) -> LocalChatAssistant {
+    let shell = environment.shell_name();
     LocalChatAssistant::new(shell)
         .with_model("gpt-4-turbo-preview")
-        .with_system(&format!("You are a {shell} programmer, respond only with commands, no explanations. Commands should be without any formatting, and ready to be copied and pasted to terminal"))
+        .with_system(&format!("You are a {shell} programmer, respond only with commands, no explanations. Commands should be without any formatting, and ready to be copied and pasted to terminal. {} Use only syntax supported by this shell and operating system, and prefer installed tools", environment.describe()))
+}
+
+pub fn explain_assistant(environment: &ShellEnvironment) -> LocalChatAssistant {
+    LocalChatAssistant::new("explain")
+        .with_model("gpt-4-turbo-preview")
+        .with_system(&format!("You are a {} expert explaining shell commands. {} Break the given command down into its programs, subcommands, flags, arguments, pipes and redirections, explaining each part in a short markdown list. Start with a one sentence summary of what the whole command does. End with a warning section if any part is destructive, irreversible, needs elevated privileges or runs downloaded code, otherwise leave it out", environment.shell_name(), environment.describe()))
+}
+
+pub fn edit_assistant() -> LocalChatAssistant {
+    LocalChatAssistant::new("editor")
+        .with_model("gpt-4-turbo-preview")
+        .with_system("You are an expert programmer editing files provided by the user, each file is given as its path followed by a fenced block with its content. Respond with a short explanation and the changes as search/replace blocks, each one is the file path on its own line followed by:\n<<<<<<< SEARCH\nexact lines from the current file\n=======\nlines replacing them\n>>>>>>> REPLACE\nSearch part must match the file exactly once, including whitespace, keep it short but unique. To create a new file use an empty search part. Only edit files provided by the user")
+}
+
+pub fn commit_message_assistant() -> LocalChatAssistant {
+    LocalChatAssistant::new("commit")
+        .with_model("gpt-4-turbo-preview")
+        .with_token_limit(500)
+        .with_system("You write git commit messages following the conventional commits specification from the given diff. First line is `type(optional scope): subject` in imperative mood, at most 72 characters, without a period, type is one of feat, fix, docs, style, refactor, perf, test, build, ci, chore. If the change is not trivial add a blank line and a body explaining what and why, wrapped at 72 characters. Respond only with the commit message, without any formatting or code fences")
+}
+
+pub fn pr_description_assistant() -> LocalChatAssistant {
+    LocalChatAssistant::new("pr-description")
+        .with_model("gpt-4-turbo-preview")
+        .with_token_limit(1500)
+        .with_system("You write pull request descriptions in markdown from the branch commit messages and diff. Start with a short title line prefixed with `# `, then a summary of what the change does and why, a list of the notable changes, and a section on how to test it. Be concise and do not invent details missing from the diff")
+}
+
+/// Summarizes part of a large diff, summaries replace the diff for other git assistants
+pub fn diff_summary_assistant() -> LocalChatAssistant {
+    LocalChatAssistant::new("diff-summary")
+        .with_model("gpt-4-turbo-preview")
+        .with_token_limit(500)
+        .with_system("You summarize part of a git diff for someone writing a commit message. For each changed file list what changed and, if apparent, why, in short bullet points. Respond only with the summary")
+}
+
+pub fn review_assistant() -> LocalChatAssistant {
+    LocalChatAssistant::new("review")
+        .with_model("gpt-4-turbo-preview")
+        .with_token_limit(2000)
+        .with_system("You are a senior engineer reviewing code changes given as a git diff or as files with numbered lines. Look for bugs, security issues, missing error handling, performance problems and unclear code, skip style nitpicks. Respond only with a JSON array of findings, each an object with `file` path, `line` number in the new version of the file, `severity` one of info, warning or error, and `suggestion` explaining the problem and how to fix it. Respond with an empty array if there is nothing to report")
 }
@@ -0,0 +1,128 @@
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+pub const CHAT_COMMANDS: &[&str] = &[
+    "/assistant",
+    "/model",
+    "/system",
+    "/clear",
+    "/undo",
+    "/retry",
+    "/save",
+    "/usage",
+    "/help",
+];
+
+#[derive(Debug, PartialEq)]
+pub enum ChatCommand {
+    Assistant(String),
+    Model(String),
+    System(Option<String>),
+    Clear,
+    Undo,
+    Retry,
+    Save(String),
+    Usage,
+    Help,
+}
+
+impl ChatCommand {
+    /// Returns `None` when input is a regular prompt instead of a known command
+    pub fn parse(input: &str) -> Result<Option<Self>> {
+        let input = input.trim();
+        let (name, argument) = match input.split_once(char::is_whitespace) {
+            None => (input, None),
+            Some((name, argument)) => (name, Some(argument.trim().to_owned())),
+        };
+
+        let command = match (name, argument) {
+            ("/assistant", Some(name)) => ChatCommand::Assistant(name),
+            ("/assistant", None) => bail!("provide assistant name, e.g. /assistant assistant"),
+            ("/model", Some(model)) => ChatCommand::Model(model),
+            ("/model", None) => bail!("provide model name, e.g. /model gpt-4-turbo-preview"),
+            ("/system", system) => ChatCommand::System(system),
+            ("/save", Some(path)) => ChatCommand::Save(path),
+            ("/save", None) => bail!("provide file path, e.g. /save chat.md"),
+            ("/clear", None) => ChatCommand::Clear,
+            ("/undo", None) => ChatCommand::Undo,
+            ("/retry", None) => ChatCommand::Retry,
+            ("/usage", None) => ChatCommand::Usage,
+            ("/help", None) => ChatCommand::Help,
+            (name, Some(_)) if CHAT_COMMANDS.contains(&name) => {
+                bail!("command {name} does not take arguments")
+            }
+            _ => return Ok(None),
+        };
+
+        Ok(Some(command))
+    }
+}
+
+pub(crate) fn chat_commands_help() -> String {
+    [
+        "/assistant <name>  switch to another assistant",
+        "/model <name>      switch model for the rest of the session",
+        "/system [prompt]   print or replace the system prompt",
+        "/clear             remove all messages from the session",
+        "/undo              remove the last prompt and answer",
+        "/retry             regenerate the last answer",
+        "/save <path>       save the transcript to a file",
+        "/usage             print token usage of the session",
+        "/help              print this help",
+    ]
+    .join("\n")
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+}
+
+impl TokenUsage {
+    pub fn total_tokens(&self) -> u32 {
+        self.prompt_tokens + self.completion_tokens
+    }
+
+    pub(crate) fn add(&mut self, prompt_tokens: u32, completion_tokens: u32) {
+        self.prompt_tokens += prompt_tokens;
+        self.completion_tokens += completion_tokens;
+    }
+}
+
+impl Display for TokenUsage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "prompt tokens: {}, completion tokens: {}, total tokens: {}",
+            self.prompt_tokens,
+            self.completion_tokens,
+            self.total_tokens()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_chat_command() -> Result<()> {
+        assert_eq!(
+            ChatCommand::parse("/model  gpt-4 ")?,
+            Some(ChatCommand::Model("gpt-4".to_owned()))
+        );
+        assert_eq!(
+            ChatCommand::parse("/system")?,
+            Some(ChatCommand::System(None))
+        );
+        assert_eq!(ChatCommand::parse(" /undo ")?, Some(ChatCommand::Undo));
+        assert_eq!(ChatCommand::parse("/usr/bin/env explain")?, None);
+        assert_eq!(ChatCommand::parse("explain {main.rs}")?, None);
+        assert!(ChatCommand::parse("/model").is_err());
+        assert!(ChatCommand::parse("/clear all").is_err());
+
+        Ok(())
+    }
+}
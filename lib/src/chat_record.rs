@@ -1,22 +1,65 @@
-use crate::KVStorage;
+use crate::{KVStorage, TokenUsage};
 use chrono::{DateTime, Local};
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::fmt::Display;
 use ulid::Ulid;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct ChatRecord {
     assistant_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
     creation_date: DateTime<Local>,
     messages: Vec<ChatMessage>,
+    /// Records saved before sessions had a status were only saved when finished
+    #[serde(default)]
+    status: SessionStatus,
+    /// Model and system prompt the session ended with, answers keep the model they were generated by
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    system_prompt: Option<String>,
+    /// System prompt was replaced during the conversation, earlier answers followed another one
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    system_prompt_changed: bool,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionStatus {
+    /// Session is in progress, or the process was killed before it could finish it
+    Active,
+    #[default]
+    Finished,
+    Errored,
+    /// Session ended with Ctrl-C while waiting for an answer, the last prompt is unanswered
+    Interrupted,
+}
+
+impl Display for SessionStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let status = match self {
+            SessionStatus::Active => "active",
+            SessionStatus::Finished => "finished",
+            SessionStatus::Errored => "errored",
+            SessionStatus::Interrupted => "interrupted",
+        };
+        write!(f, "{status}")
+    }
 }
 
 impl ChatRecord {
     pub fn new(assistant_name: &str) -> Self {
         Self {
             assistant_name: assistant_name.to_owned(),
+            title: None,
             creation_date: Local::now(),
             messages: Default::default(),
+            status: SessionStatus::Active,
+            model: None,
+            system_prompt: None,
+            system_prompt_changed: false,
         }
     }
 
@@ -25,12 +68,81 @@ impl ChatRecord {
         self
     }
 
-    pub fn add_user(&mut self, message: &str) {
-        self.messages.push(ChatMessage::new_user(message))
+    pub(crate) fn with_creation_date(mut self, creation_date: DateTime<Local>) -> Self {
+        self.creation_date = creation_date;
+        self
+    }
+
+    pub(crate) fn with_status(mut self, status: SessionStatus) -> Self {
+        self.status = status;
+        self
+    }
+
+    pub(crate) fn with_title(mut self, title: &str) -> Self {
+        self.title = Some(title.to_owned());
+        self
+    }
+
+    pub(crate) fn with_model(mut self, model: &str) -> Self {
+        self.model = Some(model.to_owned());
+        self
+    }
+
+    pub(crate) fn with_system_prompt(mut self, system_prompt: &str) -> Self {
+        self.system_prompt = Some(system_prompt.to_owned());
+        self
+    }
+
+    pub(crate) fn with_system_prompt_changed(mut self, system_prompt_changed: bool) -> Self {
+        self.system_prompt_changed = system_prompt_changed;
+        self
+    }
+
+    pub fn assistant_name(&self) -> &str {
+        &self.assistant_name
+    }
+
+    pub fn creation_date(&self) -> &DateTime<Local> {
+        &self.creation_date
     }
 
-    pub fn add_assistant(&mut self, message: &str) {
-        self.messages.push(ChatMessage::new_assistant(message))
+    pub fn status(&self) -> SessionStatus {
+        self.status
+    }
+
+    /// Title of conversations imported from other apps
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    pub fn model(&self) -> Option<&str> {
+        self.model.as_deref()
+    }
+
+    pub fn system_prompt(&self) -> Option<&str> {
+        self.system_prompt.as_deref()
+    }
+
+    pub fn system_prompt_changed(&self) -> bool {
+        self.system_prompt_changed
+    }
+
+    pub fn messages(&self) -> &[ChatMessage] {
+        &self.messages
+    }
+
+    /// Whether title or any message contains the text, ignoring case
+    pub fn contains(&self, text: &str) -> bool {
+        let text = text.to_lowercase();
+        self.title
+            .iter()
+            .map(String::as_str)
+            .chain(self.messages.iter().map(|message| message.content.as_str()))
+            .any(|content| content.to_lowercase().contains(&text))
+    }
+
+    pub(crate) fn add_message(&mut self, message: ChatMessage) {
+        self.messages.push(message)
     }
 }
 
@@ -46,39 +158,102 @@ impl Display for ChatRecord {
         writeln!(
             f,
             "Conversation with assistant \"{}\" on {}",
-            self.assistant_name,
-            self.creation_date.to_string(),
+            self.assistant_name, self.creation_date,
         )?;
+        if self.status != SessionStatus::Finished {
+            writeln!(f, "Session {}", self.status)?;
+        }
         writeln!(f, "{}", messages)
     }
 }
 
-#[derive(Serialize, Deserialize)]
-pub(crate) struct ChatMessage {
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ChatMessage {
     role: Role,
     content: String,
+    /// Prompt as it was typed, when replacing placeholders changed it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    original_content: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    timestamp: Option<DateTime<Local>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    generation: Option<Generation>,
 }
 
-#[derive(Serialize, Deserialize)]
-enum Role {
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Role {
     User,
     Assistant,
 }
 
+/// How an answer was generated, threads don't report model parameters nor usage
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Generation {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<TokenUsage>,
+    pub latency_ms: u64,
+}
+
 impl ChatMessage {
     pub fn new_assistant(content: &str) -> Self {
-        Self {
-            role: Role::Assistant,
-            content: content.to_owned(),
-        }
+        Self::new(Role::Assistant, content)
     }
 
     pub fn new_user(content: &str) -> Self {
+        Self::new(Role::User, content)
+    }
+
+    fn new(role: Role, content: &str) -> Self {
         Self {
-            role: Role::User,
+            role,
             content: content.to_owned(),
+            original_content: None,
+            timestamp: Some(Local::now()),
+            generation: None,
         }
     }
+
+    /// Keeps the typed prompt only when it differs from the sent one
+    pub(crate) fn with_original_content(mut self, original_content: &str) -> Self {
+        if original_content != self.content {
+            self.original_content = Some(original_content.to_owned());
+        }
+        self
+    }
+
+    pub(crate) fn with_timestamp(mut self, timestamp: Option<DateTime<Local>>) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+
+    pub(crate) fn with_generation(mut self, generation: Generation) -> Self {
+        self.generation = Some(generation);
+        self
+    }
+
+    pub fn role(&self) -> Role {
+        self.role
+    }
+
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+
+    pub fn original_content(&self) -> Option<&str> {
+        self.original_content.as_deref()
+    }
+
+    pub fn timestamp(&self) -> Option<&DateTime<Local>> {
+        self.timestamp.as_ref()
+    }
+
+    pub fn generation(&self) -> Option<&Generation> {
+        self.generation.as_ref()
+    }
 }
 
 impl Display for ChatMessage {
@@ -110,4 +285,117 @@ where
         let key = Ulid::new().to_string();
         self.storage.add(key, record)
     }
+
+    /// Records with their ids, oldest first
+    pub fn list(&self) -> anyhow::Result<Vec<(String, ChatRecord)>> {
+        let mut records = self.storage.get_all_entries()?;
+        // ULID ids sort by creation time
+        records.sort_by(|(a, _), (b, _)| a.cmp(b));
+        Ok(records)
+    }
+
+    pub fn get(&self, id: &str) -> anyhow::Result<Option<ChatRecord>> {
+        self.storage.get(id.to_owned())
+    }
+
+    /// Adds records with keys missing in storage, returns how many were added
+    pub fn import(&self, records: Vec<(String, ChatRecord)>) -> anyhow::Result<usize> {
+        // one read of the storage instead of a lookup per record
+        let mut existing: HashSet<String> = self
+            .storage
+            .get_all_entries()?
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect();
+
+        let mut imported = 0;
+        for (key, record) in records {
+            if existing.insert(key.to_owned()) {
+                self.storage.add(key, record)?;
+                imported += 1;
+            }
+        }
+        Ok(imported)
+    }
+
+    /// Recorder saving one record over and over as the session goes on
+    pub fn recorder(self) -> ChatRecordWriter<S> {
+        ChatRecordWriter {
+            storage: self.storage,
+            key: None,
+        }
+    }
+}
+
+/// Receives the whole record after every change of the session, so it survives crashes
+pub trait ChatRecorder {
+    fn record(&mut self, record: &ChatRecord) -> anyhow::Result<()>;
+}
+
+pub struct ChatRecordWriter<S>
+where
+    S: KVStorage<String, ChatRecord>,
+{
+    storage: S,
+    key: Option<String>,
+}
+
+impl<S> ChatRecorder for ChatRecordWriter<S>
+where
+    S: KVStorage<String, ChatRecord>,
+{
+    /// Record of ended session replaces the copies saved during it
+    fn record(&mut self, record: &ChatRecord) -> anyhow::Result<()> {
+        let key = match &self.key {
+            Some(key) => {
+                // key was added by this writer, checking it exists would read all records every turn
+                self.storage.replace(key.to_owned(), record.clone())?;
+                key.to_owned()
+            }
+            None => {
+                let key = Ulid::new().to_string();
+                self.storage.add(key.to_owned(), record.clone())?;
+                self.key = Some(key.to_owned());
+                key
+            }
+        };
+
+        match record.status() {
+            SessionStatus::Active => Ok(()),
+            _ => self.storage.compact_key(key),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deserialize_record_without_metadata() -> anyhow::Result<()> {
+        let json = r#"{
+            "assistant_name": "default",
+            "creation_date": "2024-03-20T10:00:00+01:00",
+            "messages": [{"role": "User", "content": "hi"}]
+        }"#;
+
+        let record: ChatRecord = serde_json::from_str(json)?;
+        assert_eq!(record.status(), SessionStatus::Finished);
+        assert_eq!(record.model(), None);
+        assert_eq!(record.messages()[0].timestamp(), None);
+        assert_eq!(record.messages()[0].generation(), None);
+
+        let message =
+            ChatMessage::new_user("explain main.rs").with_original_content("explain {main.rs}");
+        assert_eq!(message.original_content(), Some("explain {main.rs}"));
+        let message = ChatMessage::new_user("hi").with_original_content("hi");
+        assert_eq!(message.original_content(), None);
+
+        let record = ChatRecord::new("default").with_status(SessionStatus::Errored);
+        let json = serde_json::to_string(&record)?;
+        assert!(json.contains(r#""status":"errored""#));
+        let record = ChatRecord::new("default").with_status(SessionStatus::Interrupted);
+        assert!(record.to_string().contains("Session interrupted"));
+        Ok(())
+    }
 }
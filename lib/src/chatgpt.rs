@@ -0,0 +1,367 @@
+use crate::{ChatMessage, ChatRecord, SessionStatus};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Local};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+use ulid::Ulid;
+use zip::ZipArchive;
+
+pub const CHATGPT_ASSISTANT_NAME: &str = "chatgpt";
+const CONVERSATIONS_ENTRY: &str = "conversations.json";
+
+/// Conversation from `conversations.json` of ChatGPT data export, messages form a tree of edits
+#[derive(Deserialize)]
+struct Conversation {
+    #[serde(default)]
+    title: Option<String>,
+    create_time: f64,
+    #[serde(default)]
+    conversation_id: Option<String>,
+    #[serde(default)]
+    id: Option<String>,
+    mapping: HashMap<String, Node>,
+    #[serde(default)]
+    current_node: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Node {
+    #[serde(default)]
+    message: Option<Message>,
+    #[serde(default)]
+    parent: Option<String>,
+    #[serde(default)]
+    children: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct Message {
+    author: Author,
+    content: Content,
+    #[serde(default)]
+    create_time: Option<f64>,
+    #[serde(default)]
+    metadata: HashMap<String, Value>,
+}
+
+#[derive(Deserialize)]
+struct Author {
+    role: String,
+}
+
+#[derive(Deserialize)]
+struct Content {
+    content_type: String,
+    #[serde(default)]
+    parts: Vec<Value>,
+}
+
+impl Message {
+    /// Text of the message, images and other attachments are left out
+    fn text(&self) -> Option<String> {
+        if !matches!(
+            self.content.content_type.as_str(),
+            "text" | "multimodal_text"
+        ) {
+            return None;
+        }
+        let hidden = self
+            .metadata
+            .get("is_visually_hidden_from_conversation")
+            .and_then(Value::as_bool)
+            .unwrap_or_default();
+        if hidden {
+            return None;
+        }
+
+        let text = self
+            .content
+            .parts
+            .iter()
+            .filter_map(Value::as_str)
+            .collect::<Vec<_>>()
+            .join("\n");
+        Some(text).filter(|text| !text.trim().is_empty())
+    }
+
+    fn model(&self) -> Option<&str> {
+        self.metadata.get("model_slug").and_then(Value::as_str)
+    }
+}
+
+impl Conversation {
+    /// Messages on the branch that was shown last, older edits and regenerations are dropped
+    fn main_branch(&self) -> Result<Vec<&Message>> {
+        let mut node_id = self.current_node.to_owned().or_else(|| self.last_leaf());
+        let mut visited = HashSet::new();
+        let mut messages = vec![];
+
+        while let Some((id, node)) = node_id.and_then(|id| self.mapping.get_key_value(&id)) {
+            if !visited.insert(id) {
+                bail!("parent of message \"{id}\" leads back to it");
+            }
+            messages.extend(&node.message);
+            node_id = node.parent.to_owned();
+        }
+
+        messages.reverse();
+        Ok(messages)
+    }
+
+    /// Leaf reached from the root through the newest children, for exports without current node
+    fn last_leaf(&self) -> Option<String> {
+        let (mut id, mut node) = self
+            .mapping
+            .iter()
+            .find(|(_, node)| node.parent.is_none())?;
+        let mut visited = HashSet::from([id]);
+        while let Some(child) = node.children.last() {
+            (id, node) = self.mapping.get_key_value(child)?;
+            if !visited.insert(id) {
+                return None;
+            }
+        }
+        Some(id.to_owned())
+    }
+
+    /// ULID from creation time and conversation id, so importing the same export twice finds it
+    fn key(&self) -> String {
+        let timestamp_ms = (self.create_time * 1000.0) as u64;
+        let id = self
+            .conversation_id
+            .as_deref()
+            .or(self.id.as_deref())
+            .unwrap_or_default();
+
+        Ulid::from_parts(timestamp_ms, fnv1a(id).into()).to_string()
+    }
+
+    fn into_chat_record(self) -> Result<(String, ChatRecord)> {
+        let key = self.key();
+        let mut record = ChatRecord::new(CHATGPT_ASSISTANT_NAME)
+            .with_creation_date(to_local(self.create_time))
+            .with_status(SessionStatus::Finished);
+        if let Some(title) = &self.title {
+            record = record.with_title(title);
+        }
+
+        let mut model = None;
+        for message in self.main_branch()? {
+            let Some(text) = message.text() else {
+                continue;
+            };
+            let chat_message = match message.author.role.as_str() {
+                "system" => {
+                    record = record.with_system_prompt(&text);
+                    continue;
+                }
+                "user" => ChatMessage::new_user(&text),
+                "assistant" => {
+                    model = message.model().or(model);
+                    ChatMessage::new_assistant(&text)
+                }
+                _ => continue,
+            };
+            record.add_message(chat_message.with_timestamp(message.create_time.map(to_local)));
+        }
+
+        if let Some(model) = model {
+            record = record.with_model(model);
+        }
+        Ok((key, record))
+    }
+}
+
+/// Records read from ChatGPT data export with the conversations that could not be read
+#[derive(Default)]
+pub struct ChatGptExport {
+    pub records: Vec<(String, ChatRecord)>,
+    /// Position and title of skipped conversations with the reason
+    pub skipped: Vec<String>,
+}
+
+/// Reads `conversations.json` itself or the whole export archive containing it
+pub fn read_chatgpt_export(path: &Path) -> Result<ChatGptExport> {
+    let json = match path.extension().is_some_and(|extension| extension == "zip") {
+        true => {
+            let mut archive = ZipArchive::new(File::open(path)?)
+                .with_context(|| format!("file {path:?} is not a valid zip archive"))?;
+            let mut json = String::new();
+            archive
+                .by_name(CONVERSATIONS_ENTRY)
+                .with_context(|| format!("archive {path:?} has no {CONVERSATIONS_ENTRY}"))?
+                .read_to_string(&mut json)?;
+            json
+        }
+        false => fs::read_to_string(path).with_context(|| format!("failed to read {path:?}"))?,
+    };
+
+    parse_chatgpt_export(&json)
+}
+
+/// Chat records with their keys from `conversations.json` of ChatGPT data export,
+/// conversations in unexpected format are skipped without failing the others
+pub fn parse_chatgpt_export(json: &str) -> Result<ChatGptExport> {
+    let conversations: Vec<Value> =
+        serde_json::from_str(json).context("not a ChatGPT conversations.json export")?;
+
+    let mut export = ChatGptExport::default();
+    for (index, conversation) in conversations.into_iter().enumerate() {
+        let title = conversation
+            .get("title")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_owned();
+        let record = serde_json::from_value::<Conversation>(conversation)
+            .map_err(anyhow::Error::from)
+            .and_then(Conversation::into_chat_record);
+        match record {
+            Ok((key, record)) => {
+                if !record.messages().is_empty() {
+                    export.records.push((key, record));
+                }
+            }
+            Err(err) => export
+                .skipped
+                .push(format!("conversation {} \"{title}\": {err}", index + 1)),
+        }
+    }
+
+    Ok(export)
+}
+
+/// Hash stable across Rust releases, unlike the standard library one
+fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn to_local(timestamp: f64) -> DateTime<Local> {
+    let seconds = timestamp.trunc() as i64;
+    let nanos = (timestamp.fract() * 1e9) as u32;
+    DateTime::from_timestamp(seconds, nanos)
+        .unwrap_or_default()
+        .with_timezone(&Local)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Role;
+    use serde_json::json;
+
+    const EXPORT: &str = r#"[{
+        "title": "Rust lifetimes",
+        "create_time": 1700000000.5,
+        "conversation_id": "6554a1f0-0000-0000-0000-000000000000",
+        "current_node": "answer-2",
+        "mapping": {
+            "root": {"id": "root", "message": null, "parent": null, "children": ["system"]},
+            "system": {
+                "id": "system",
+                "message": {
+                    "author": {"role": "system"},
+                    "content": {"content_type": "text", "parts": [""]},
+                    "metadata": {"is_visually_hidden_from_conversation": true}
+                },
+                "parent": "root",
+                "children": ["question"]
+            },
+            "question": {
+                "id": "question",
+                "message": {
+                    "author": {"role": "user"},
+                    "content": {"content_type": "text", "parts": ["What is 'a?"]},
+                    "create_time": 1700000001.0
+                },
+                "parent": "system",
+                "children": ["answer-1", "answer-2"]
+            },
+            "answer-1": {
+                "id": "answer-1",
+                "message": {
+                    "author": {"role": "assistant"},
+                    "content": {"content_type": "text", "parts": ["Discarded answer"]},
+                    "metadata": {"model_slug": "gpt-4"}
+                },
+                "parent": "question",
+                "children": []
+            },
+            "answer-2": {
+                "id": "answer-2",
+                "message": {
+                    "author": {"role": "assistant"},
+                    "content": {"content_type": "text", "parts": ["A lifetime."]},
+                    "metadata": {"model_slug": "gpt-4o"}
+                },
+                "parent": "question",
+                "children": []
+            }
+        }
+    }]"#;
+
+    #[test]
+    fn test_parse_chatgpt_export() -> Result<()> {
+        let records = parse_chatgpt_export(EXPORT)?.records;
+
+        assert_eq!(records.len(), 1);
+        let (key, record) = &records[0];
+        assert_eq!(record.title(), Some("Rust lifetimes"));
+        assert_eq!(record.model(), Some("gpt-4o"));
+        assert_eq!(record.system_prompt(), None);
+
+        let messages: Vec<_> = record
+            .messages()
+            .iter()
+            .map(|message| (message.role(), message.content()))
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                (Role::User, "What is 'a?"),
+                (Role::Assistant, "A lifetime.")
+            ]
+        );
+
+        assert_eq!(key, &parse_chatgpt_export(EXPORT)?.records[0].0);
+        assert_eq!(Ulid::from_string(key)?.timestamp_ms(), 1700000000500);
+        Ok(())
+    }
+
+    #[test]
+    fn test_skip_malformed_conversations() -> Result<()> {
+        let conversations: Vec<Value> = serde_json::from_str(EXPORT)?;
+        let json = serde_json::to_string(&[
+            json!({"title": "Broken", "mapping": {}}),
+            conversations[0].to_owned(),
+        ])?;
+
+        let export = parse_chatgpt_export(&json)?;
+
+        assert_eq!(export.records.len(), 1);
+        assert_eq!(export.skipped.len(), 1);
+        assert!(export.skipped[0].starts_with("conversation 1 \"Broken\": missing field"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_skip_conversations_with_parent_cycle() -> Result<()> {
+        let mut conversations: Vec<Value> = serde_json::from_str(EXPORT)?;
+        let mut cycle = conversations[0].to_owned();
+        cycle["title"] = json!("Cycle");
+        cycle["mapping"]["system"]["parent"] = json!("question");
+        conversations.insert(0, cycle);
+
+        let export = parse_chatgpt_export(&serde_json::to_string(&conversations)?)?;
+
+        assert_eq!(export.records.len(), 1);
+        assert_eq!(export.skipped.len(), 1);
+        assert!(export.skipped[0].starts_with("conversation 1 \"Cycle\": parent of message"));
+        Ok(())
+    }
+}
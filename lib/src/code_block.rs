@@ -0,0 +1,98 @@
+const FENCES: [&str; 2] = ["```", "~~~"];
+const SHELL_LANGUAGES: &[&str] = &[
+    "sh",
+    "bash",
+    "zsh",
+    "fish",
+    "shell",
+    "console",
+    "powershell",
+    "pwsh",
+    "ps1",
+];
+
+#[derive(Debug, PartialEq)]
+pub struct CodeBlock {
+    pub language: Option<String>,
+    pub code: String,
+}
+
+impl CodeBlock {
+    pub fn is_diff(&self) -> bool {
+        matches!(self.language.as_deref(), Some("diff") | Some("patch"))
+            || self.code.lines().any(|line| line.starts_with("@@"))
+    }
+
+    /// Only blocks labeled with a shell language, unlabeled ones may be prose or any code
+    pub fn is_shell(&self) -> bool {
+        self.language
+            .as_deref()
+            .is_some_and(|language| SHELL_LANGUAGES.contains(&language))
+    }
+}
+
+/// Returns fenced code blocks in the order they appear in the markdown text
+pub fn parse_code_blocks(text: &str) -> Vec<CodeBlock> {
+    let mut blocks = vec![];
+    let mut current: Option<(String, CodeBlock)> = None;
+
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        match &mut current {
+            None => {
+                let Some(fence) = opening_fence(trimmed) else {
+                    continue;
+                };
+                let language = trimmed[fence.len()..].split_whitespace().next();
+                current = Some((
+                    fence,
+                    CodeBlock {
+                        language: language.map(|l| l.to_lowercase()),
+                        code: String::new(),
+                    },
+                ));
+            }
+            Some((fence, _)) if trimmed.trim_end() == fence => {
+                if let Some((_, block)) = current.take() {
+                    blocks.push(block);
+                }
+            }
+            Some((_, block)) => {
+                block.code.push_str(line);
+                block.code.push('\n');
+            }
+        }
+    }
+
+    blocks
+}
+
+fn opening_fence(line: &str) -> Option<String> {
+    FENCES.iter().find_map(|fence| {
+        let fence_char = fence.chars().next()?;
+        let length = line.chars().take_while(|c| *c == fence_char).count();
+        (length >= fence.len()).then(|| fence_char.to_string().repeat(length))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_code_blocks() {
+        let text = "Run this:\n```sh\nls -la\n```\nthen\n````Rust title\nfn main() {}\n```\n````\n~~~\nplain\n~~~";
+        let blocks = parse_code_blocks(text);
+
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(blocks[0].language.as_deref(), Some("sh"));
+        assert_eq!(blocks[0].code, "ls -la\n");
+        assert_eq!(blocks[1].language.as_deref(), Some("rust"));
+        assert_eq!(blocks[1].code, "fn main() {}\n```\n");
+        assert_eq!(blocks[2].language, None);
+        assert!(blocks[0].is_shell());
+        assert!(!blocks[1].is_shell());
+        assert!(!blocks[2].is_shell());
+        assert!(parse_code_blocks("no code ``` here").is_empty());
+    }
+}
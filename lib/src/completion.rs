@@ -1,4 +1,5 @@
 use anyhow::Context;
+use itertools::Itertools;
 use regex::Regex;
 use std::path::PathBuf;
 use std::{env, fs, io, path};
@@ -21,20 +22,17 @@ impl TryFrom<&str> for IncompleteFilePath {
             .captures(input)
             .context("not found incomplete path")?;
 
-        let complete_path = match captures.name("complete_path") {
-            None => None,
-            Some(matched) => Some(matched.as_str().to_owned()),
-        };
+        let complete_path = captures
+            .name("complete_path")
+            .map(|matched| matched.as_str().to_owned());
 
-        let incomplete_path = match captures.name("incomplete_path") {
-            None => None,
-            Some(matched) => Some(matched.as_str().to_owned()),
-        };
+        let incomplete_path = captures
+            .name("incomplete_path")
+            .map(|matched| matched.as_str().to_owned());
 
-        let rest = match captures.name("rest") {
-            None => None,
-            Some(matched) => Some(matched.as_str().to_owned()),
-        };
+        let rest = captures
+            .name("rest")
+            .map(|matched| matched.as_str().to_owned());
 
         Ok(Self {
             complete_path,
@@ -44,52 +42,124 @@ impl TryFrom<&str> for IncompleteFilePath {
     }
 }
 
-pub fn get_path_completion(input: &str) -> anyhow::Result<String> {
+/// Returns completed inputs ranked by prefix matches first, then fuzzy subsequence matches.
+/// Hidden entries are listed only once the typed name starts with a dot
+pub fn get_path_completions(input: &str) -> anyhow::Result<Vec<String>> {
     if input.is_empty() || !has_incomplete_path(input) {
-        return Ok(input.to_owned());
+        return Ok(vec![]);
     }
 
     let path = IncompleteFilePath::try_from(input)?;
+    let complete_path = path.complete_path.as_deref().map(expand_home);
     let incomplete_path_name = path.incomplete_path.as_deref().unwrap_or_default();
+    let show_hidden = incomplete_path_name.starts_with('.');
 
-    let entries = get_directory_entry_paths(path.complete_path.as_ref())?;
-    let matching_entry_names: Vec<String> = entries
+    let mut entries: Vec<(String, bool)> = get_directory_entry_paths(complete_path.as_ref())?
         .into_iter()
-        .filter_map(|entry| match entry.file_name() {
-            Some(file_name) => Some((
-                file_name.to_str().unwrap_or_default().to_owned(),
-                entry.is_dir(),
-            )),
-            None => None,
+        .filter_map(|entry| {
+            entry.file_name().map(|file_name| {
+                (
+                    file_name.to_str().unwrap_or_default().to_owned(),
+                    entry.is_dir(),
+                )
+            })
         })
-        .filter(|(file_name, _)| file_name.starts_with(incomplete_path_name))
-        .map(|(file_name, is_dir)| {
-            if is_dir {
-                format!("{file_name}/")
-            } else {
+        .filter(|(file_name, _)| show_hidden || !file_name.starts_with('.'))
+        .collect();
+    entries.sort();
+
+    let completions = entries
+        .into_iter()
+        .filter_map(|(file_name, is_dir)| {
+            let rank = match_rank(&file_name, incomplete_path_name)?;
+            Some((rank, file_name, is_dir))
+        })
+        .sorted_by_key(|(rank, _, _)| *rank)
+        .map(|(_, file_name, is_dir)| {
+            let file_name = match is_dir {
+                true => format!("{file_name}/"),
+                false => file_name,
+            };
+            format!(
+                "{}{}{}",
+                path.input_before,
+                complete_path.as_deref().unwrap_or_default(),
                 file_name
-            }
+            )
         })
         .collect();
 
-    if matching_entry_names.is_empty() {
-        return Ok(input.to_owned());
+    Ok(completions)
+}
+
+pub fn longest_common_prefix(values: &[String]) -> String {
+    let Some((first, rest)) = values.split_first() else {
+        return String::new();
+    };
+
+    let prefix_len = rest.iter().fold(first.len(), |prefix_len, value| {
+        first
+            .char_indices()
+            .zip(value.chars())
+            .take_while(|((index, a), b)| *index < prefix_len && a == b)
+            .map(|((index, a), _)| index + a.len_utf8())
+            .last()
+            .unwrap_or_default()
+    });
+
+    first[..prefix_len].to_owned()
+}
+
+/// Lower rank is a better match, `None` when name does not match at all
+pub(crate) fn match_rank(name: &str, pattern: &str) -> Option<(u8, usize)> {
+    if name.starts_with(pattern) {
+        return Some((0, 0));
     }
+    if name.to_lowercase().starts_with(&pattern.to_lowercase()) {
+        return Some((1, 0));
+    }
+
+    fuzzy_match_span(name, pattern).map(|span| (2, span))
+}
 
-    let completion = format!(
-        "{}{}{}",
-        path.input_before,
-        path.complete_path.as_deref().unwrap_or_default(),
-        &matching_entry_names[0]
-    );
+/// Length of the shortest name fragment containing pattern characters in order
+fn fuzzy_match_span(name: &str, pattern: &str) -> Option<usize> {
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let first = *pattern.first()?;
+
+    (0..name.len())
+        .filter(|start| name[*start] == first)
+        .filter_map(|start| {
+            let mut pattern_chars = pattern.iter().peekable();
+            let end = name[start..].iter().position(|c| {
+                if pattern_chars.peek() == Some(&c) {
+                    pattern_chars.next();
+                }
+                pattern_chars.peek().is_none()
+            })?;
+            Some(end + 1)
+        })
+        .min()
+}
+
+fn expand_home(path: &str) -> String {
+    let Some(rest) = path.strip_prefix('~') else {
+        return path.to_owned();
+    };
 
-    Ok(completion)
+    match dirs::home_dir() {
+        Some(home) if rest.is_empty() || rest.starts_with(['/', '\\']) => {
+            format!("{}{rest}", home.display())
+        }
+        _ => path.to_owned(),
+    }
 }
 
 fn has_incomplete_path(input: &str) -> bool {
     Regex::new(INCOMPLETE_FILE_PATH_PATTERN)
         .unwrap()
-        .is_match(&input)
+        .is_match(input)
 }
 
 fn get_directory_entry_paths(path: Option<&String>) -> io::Result<Vec<PathBuf>> {
@@ -99,7 +169,6 @@ fn get_directory_entry_paths(path: Option<&String>) -> io::Result<Vec<PathBuf>>
     };
 
     let entry_paths = fs::read_dir(current_path)?
-        .into_iter()
         .filter_map(|entry| entry.ok())
         .map(|entry| entry.path())
         .collect();
@@ -112,17 +181,34 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_get_path_completion() -> anyhow::Result<()> {
-        assert_eq!(get_path_completion("{Carg")?, "{Cargo.toml".to_owned());
+    fn test_get_path_completions() -> anyhow::Result<()> {
+        assert_eq!(get_path_completions("{Carg")?[0], "{Cargo.toml");
         assert_eq!(
-            get_path_completion("some text {src")?,
-            "some text {src/".to_owned()
+            get_path_completions("some text {src")?[0],
+            "some text {src/"
         );
         assert_eq!(
-            get_path_completion("some text {src/comp")?,
-            "some text {src/completion.rs".to_owned()
+            get_path_completions("some text {src/comp")?[0],
+            "some text {src/completion.rs"
         );
+        assert_eq!(
+            get_path_completions("{src/plac")?,
+            vec!["{src/placeholder/", "{src/placeholder.rs"]
+        );
+        assert_eq!(
+            get_path_completions("{src/chtrec")?,
+            vec!["{src/chat_record.rs"]
+        );
+        assert!(get_path_completions("{src/xyz")?.is_empty());
 
         Ok(())
     }
+
+    #[test]
+    fn test_longest_common_prefix() {
+        let values = ["{src/placeholder/", "{src/placeholder.rs"].map(String::from);
+        assert_eq!(longest_common_prefix(&values), "{src/placeholder");
+        assert_eq!(longest_common_prefix(&values[..1]), "{src/placeholder/");
+        assert_eq!(longest_common_prefix(&[]), "");
+    }
 }
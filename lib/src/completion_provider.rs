@@ -0,0 +1,171 @@
+use crate::completion::{get_path_completions, match_rank};
+use itertools::Itertools;
+use std::path::Path;
+
+pub trait CompletionProvider {
+    /// Returns ranked completed inputs, empty when provider does not apply to the input
+    fn completions(&self, input: &str) -> anyhow::Result<Vec<String>>;
+}
+
+pub struct PathCompletionProvider;
+
+impl CompletionProvider for PathCompletionProvider {
+    fn completions(&self, input: &str) -> anyhow::Result<Vec<String>> {
+        get_path_completions(input)
+    }
+}
+
+/// Completes command names at the start of the input, e.g. `/mo` to `/model`
+pub struct CommandCompletionProvider {
+    commands: Vec<String>,
+}
+
+impl CommandCompletionProvider {
+    pub fn new<S: ToString>(commands: &[S]) -> Self {
+        Self {
+            commands: commands.iter().map(|c| c.to_string()).collect(),
+        }
+    }
+}
+
+impl CompletionProvider for CommandCompletionProvider {
+    fn completions(&self, input: &str) -> anyhow::Result<Vec<String>> {
+        if !input.starts_with('/') || input.contains(char::is_whitespace) {
+            return Ok(vec![]);
+        }
+
+        Ok(rank_values(&self.commands, input))
+    }
+}
+
+/// Completes the argument of a single command, e.g. assistant names after `/assistant `
+pub struct ArgumentCompletionProvider {
+    command: String,
+    values: Vec<String>,
+}
+
+impl ArgumentCompletionProvider {
+    pub fn new<S: ToString>(command: &str, values: &[S]) -> Self {
+        Self {
+            command: command.to_owned(),
+            values: values.iter().map(|v| v.to_string()).collect(),
+        }
+    }
+}
+
+impl CompletionProvider for ArgumentCompletionProvider {
+    fn completions(&self, input: &str) -> anyhow::Result<Vec<String>> {
+        let Some(argument) = input
+            .strip_prefix(&self.command)
+            .and_then(|rest| rest.strip_prefix(' '))
+        else {
+            return Ok(vec![]);
+        };
+
+        let completions = rank_values(&self.values, argument.trim_start())
+            .into_iter()
+            .map(|value| format!("{} {value}", self.command))
+            .collect();
+
+        Ok(completions)
+    }
+}
+
+/// Completes file placeholders to their kinds, the whole file `{main.rs}` or its slice
+/// `{main.rs 1:}`, and closes slices typed after the path
+pub struct PlaceholderCompletionProvider;
+
+impl CompletionProvider for PlaceholderCompletionProvider {
+    fn completions(&self, input: &str) -> anyhow::Result<Vec<String>> {
+        let Some(open_index) = input.rfind('{') else {
+            return Ok(vec![]);
+        };
+        let (input_before, placeholder) = input.split_at(open_index + 1);
+        if placeholder.contains('}') {
+            return Ok(vec![]);
+        }
+
+        if is_file(placeholder) {
+            return Ok(vec![
+                format!("{input_before}{placeholder}}}"),
+                format!("{input_before}{placeholder} 1:}}"),
+            ]);
+        }
+        let Some((path, slice)) = placeholder
+            .rsplit_once(' ')
+            .filter(|(path, _)| is_file(path.trim_end()))
+        else {
+            return Ok(vec![]);
+        };
+
+        let path = path.trim_end();
+        let completion = match slice.split_once(':') {
+            None if slice.chars().all(|c| c.is_ascii_digit()) => {
+                let from = if slice.is_empty() { "1" } else { slice };
+                format!("{input_before}{path} {from}:}}")
+            }
+            Some((from, to)) if from.chars().chain(to.chars()).all(|c| c.is_ascii_digit()) => {
+                format!("{input_before}{path} {slice}}}")
+            }
+            _ => return Ok(vec![]),
+        };
+
+        Ok(vec![completion])
+    }
+}
+
+fn is_file(path: &str) -> bool {
+    !path.is_empty() && Path::new(path).is_file()
+}
+
+fn rank_values(values: &[String], pattern: &str) -> Vec<String> {
+    values
+        .iter()
+        .filter_map(|value| Some((match_rank(value, pattern)?, value)))
+        .sorted_by_key(|(rank, _)| *rank)
+        .map(|(_, value)| value.to_owned())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_argument_completions() -> anyhow::Result<()> {
+        let provider = ArgumentCompletionProvider::new("/model", &["gpt-4", "gpt-3.5-turbo"]);
+
+        assert_eq!(
+            provider.completions("/model gpt-3")?,
+            vec!["/model gpt-3.5-turbo"]
+        );
+        assert_eq!(provider.completions("/model ")?.len(), 2);
+        assert!(provider.completions("/modelgpt")?.is_empty());
+        assert!(provider.completions("gpt")?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_placeholder_completions() -> anyhow::Result<()> {
+        let provider = PlaceholderCompletionProvider;
+
+        assert_eq!(
+            provider.completions("read {Cargo.toml")?,
+            vec!["read {Cargo.toml}", "read {Cargo.toml 1:}"]
+        );
+        assert_eq!(
+            provider.completions("read {Cargo.toml 3")?,
+            vec!["read {Cargo.toml 3:}"]
+        );
+        assert_eq!(
+            provider.completions("read {Cargo.toml 3:5")?,
+            vec!["read {Cargo.toml 3:5}"]
+        );
+        assert!(provider.completions("read {src")?.is_empty());
+        assert!(provider.completions("read {Cargo.toml}")?.is_empty());
+        assert!(provider.completions("read {Cargo.toml x")?.is_empty());
+
+        Ok(())
+    }
+}
@@ -1,37 +1,264 @@
 use crate::storage::Storage;
-use crate::APP_NAME;
-use anyhow::{Context, Result};
+use crate::{
+    command_api_key, env_api_key, user_shell, Budgets, CommandPolicy, Keyring, API_KEY_ENV,
+    APP_NAME,
+};
+use anyhow::{bail, Context, Result};
 use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+const DEFAULT_HISTORY_SIZE: u16 = 100;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ExpliceConfig {
+    /// Empty when the key comes from keyring or command
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     api_key: String,
+    /// Command printing the key, e.g. `pass show openai`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    api_key_cmd: Option<String>,
+    #[serde(default)]
+    api_key_keyring: bool,
     token_limit: u16,
+    #[serde(default = "default_history_size")]
+    history_size: u16,
+    #[serde(default)]
+    raw_output: bool,
+    #[serde(default)]
+    shell_allow: Vec<String>,
+    #[serde(default)]
+    shell_deny: Vec<String>,
+    #[serde(default)]
+    shell: Option<String>,
+    #[serde(default)]
+    storage_backend: StorageBackend,
+    #[serde(default)]
+    budgets: Budgets,
+}
+
+/// Where assistants, chat records and prompt history are kept, config itself is always a JSON file
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    #[default]
+    Json,
+    Sqlite,
+}
+
+impl FromStr for StorageBackend {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "json" => Ok(StorageBackend::Json),
+            "sqlite" => Ok(StorageBackend::Sqlite),
+            _ => bail!("unknown storage backend \"{value}\", use json or sqlite"),
+        }
+    }
+}
+
+impl Display for StorageBackend {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StorageBackend::Json => write!(f, "json"),
+            StorageBackend::Sqlite => write!(f, "sqlite"),
+        }
+    }
+}
+
+fn default_history_size() -> u16 {
+    DEFAULT_HISTORY_SIZE
 }
 
 impl ExpliceConfig {
-    pub fn api_key(&self) -> &str {
-        &self.api_key
+    /// Key from `OPENAI_API_KEY`, `api_key_cmd`, keyring or config file, in this order
+    pub fn api_key(&self) -> Result<String> {
+        self.resolve_api_key(env_api_key(), &user_shell(self.shell()), Keyring::get)
+    }
+
+    /// Environment, shell and keyring come from the caller, so tests don't touch the real ones
+    fn resolve_api_key(
+        &self,
+        env_api_key: Option<String>,
+        shell: &str,
+        keyring_api_key: impl FnOnce() -> Result<Option<String>>,
+    ) -> Result<String> {
+        if let Some(api_key) = env_api_key {
+            return Ok(api_key);
+        }
+        if let Some(command) = &self.api_key_cmd {
+            return command_api_key(shell, command);
+        }
+        if self.api_key_keyring {
+            return keyring_api_key()?.with_context(|| {
+                format!("no API key in keyring, run \"{APP_NAME} config --api-key\" to store it")
+            });
+        }
+        if self.api_key.is_empty() {
+            bail!(
+                "no API key configured, set {API_KEY_ENV} or run \"{APP_NAME} config --api-key\""
+            );
+        }
+        Ok(self.api_key.to_owned())
     }
 
     pub fn token_limit(&self) -> &u16 {
         &self.token_limit
     }
 
+    pub fn history_size(&self) -> usize {
+        self.history_size.into()
+    }
+
+    pub fn raw_output(&self) -> bool {
+        self.raw_output
+    }
+
+    /// Shell used to run commands instead of the detected one
+    pub fn shell(&self) -> Option<&str> {
+        self.shell.as_deref()
+    }
+
+    pub fn storage_backend(&self) -> StorageBackend {
+        self.storage_backend
+    }
+
+    pub fn budgets(&self) -> &Budgets {
+        &self.budgets
+    }
+
+    /// Commands from `explice sh` checked against user allow and deny lists
+    pub fn command_policy(&self) -> CommandPolicy {
+        CommandPolicy::new(&self.shell_allow, &self.shell_deny)
+    }
+
     pub fn new(api_key: String, token_limit: u16) -> Self {
         ExpliceConfig {
             api_key,
+            api_key_cmd: None,
+            api_key_keyring: false,
             token_limit,
+            history_size: DEFAULT_HISTORY_SIZE,
+            raw_output: false,
+            shell_allow: vec![],
+            shell_deny: vec![],
+            shell: None,
+            storage_backend: StorageBackend::default(),
+            budgets: Budgets::default(),
         }
     }
 
-    fn update(&mut self, api_key: Option<&str>, token_limit: Option<u16>) {
-        if let Some(api_key) = api_key {
-            self.api_key = api_key.to_owned();
+    /// Keyring is only read here, its change is returned to be made around writing the config.
+    /// Keyring switch is applied before the key, so a new key goes straight to its store
+    fn update(&mut self, mut update: ExpliceConfigUpdate) -> Result<Option<KeyringChange>> {
+        let mut keyring_change = None;
+        match update.api_key_keyring {
+            Some(true) if !self.api_key_keyring => {
+                let api_key = match update.api_key.take() {
+                    Some(api_key) => api_key,
+                    None => std::mem::take(&mut self.api_key),
+                };
+                if !api_key.is_empty() {
+                    keyring_change = Some(KeyringChange::Store(api_key));
+                }
+                self.api_key.clear();
+                self.api_key_keyring = true;
+            }
+            Some(false) if self.api_key_keyring => {
+                self.api_key = Keyring::get()?.unwrap_or_default();
+                keyring_change = Some(KeyringChange::Delete);
+                self.api_key_keyring = false;
+            }
+            _ => {}
+        }
+        if let Some(api_key) = update.api_key {
+            match self.api_key_keyring {
+                true => keyring_change = Some(KeyringChange::Store(api_key)),
+                false => self.api_key = api_key,
+            }
+        };
+        if let Some(api_key_cmd) = update.api_key_cmd {
+            self.api_key_cmd = Some(api_key_cmd).filter(|command| !command.trim().is_empty());
+        };
+        if let Some(token_limit) = update.token_limit {
+            self.token_limit = token_limit;
+        };
+        if let Some(history_size) = update.history_size {
+            self.history_size = history_size;
+        };
+        if let Some(raw_output) = update.raw_output {
+            self.raw_output = raw_output;
+        };
+        if let Some(shell_allow) = update.shell_allow {
+            self.shell_allow = shell_allow;
+        };
+        if let Some(shell_deny) = update.shell_deny {
+            self.shell_deny = shell_deny;
         };
-        if let Some(token_limit) = token_limit {
-            self.token_limit = token_limit.to_owned();
+        if let Some(shell) = update.shell {
+            self.shell = Some(shell).filter(|shell| !shell.is_empty());
         };
+        if let Some(storage_backend) = update.storage_backend {
+            self.storage_backend = storage_backend;
+        };
+        // zero removes the budget
+        let budgets = [
+            (update.budget_daily_soft, &mut self.budgets.daily_soft),
+            (update.budget_daily_hard, &mut self.budgets.daily_hard),
+            (update.budget_monthly_soft, &mut self.budgets.monthly_soft),
+            (update.budget_monthly_hard, &mut self.budgets.monthly_hard),
+        ];
+        for (update, budget) in budgets {
+            if let Some(limit) = update {
+                *budget = Some(limit).filter(|limit| *limit > 0.0);
+            }
+        }
+        Ok(keyring_change)
+    }
+}
+
+/// Keyring change of a config update
+#[derive(Debug, PartialEq)]
+enum KeyringChange {
+    Store(String),
+    Delete,
+}
+
+#[derive(Debug, Default)]
+pub struct ExpliceConfigUpdate {
+    pub api_key: Option<String>,
+    pub api_key_cmd: Option<String>,
+    pub api_key_keyring: Option<bool>,
+    pub token_limit: Option<u16>,
+    pub history_size: Option<u16>,
+    pub raw_output: Option<bool>,
+    pub shell_allow: Option<Vec<String>>,
+    pub shell_deny: Option<Vec<String>>,
+    pub shell: Option<String>,
+    pub storage_backend: Option<StorageBackend>,
+    pub budget_daily_soft: Option<f64>,
+    pub budget_daily_hard: Option<f64>,
+    pub budget_monthly_soft: Option<f64>,
+    pub budget_monthly_hard: Option<f64>,
+}
+
+impl ExpliceConfigUpdate {
+    pub fn is_empty(&self) -> bool {
+        self.api_key.is_none()
+            && self.api_key_cmd.is_none()
+            && self.api_key_keyring.is_none()
+            && self.token_limit.is_none()
+            && self.history_size.is_none()
+            && self.raw_output.is_none()
+            && self.shell_allow.is_none()
+            && self.shell_deny.is_none()
+            && self.shell.is_none()
+            && self.storage_backend.is_none()
+            && self.budget_daily_soft.is_none()
+            && self.budget_daily_hard.is_none()
+            && self.budget_monthly_soft.is_none()
+            && self.budget_monthly_hard.is_none()
     }
 }
 
@@ -59,10 +286,21 @@ where
         self.storage.write(&config)
     }
 
-    pub fn update(&self, api_key: Option<&str>, token_limit: Option<u16>) -> Result<()> {
+    /// Key leaves its old store only once the new one holds it: keyring gets the key
+    /// before the config stops keeping it, and loses it after the config was written
+    pub fn update(&self, update: ExpliceConfigUpdate) -> Result<()> {
         let mut config = self.storage.read()?.context("no config found")?;
-        config.update(api_key, token_limit);
-        self.storage.write(&config)
+        match config.update(update)? {
+            Some(KeyringChange::Store(api_key)) => {
+                Keyring::set(&api_key)?;
+                self.storage.write(&config)
+            }
+            Some(KeyringChange::Delete) => {
+                self.storage.write(&config)?;
+                Keyring::delete().context("API key was moved to the config file")
+            }
+            None => self.storage.write(&config),
+        }
     }
 
     pub fn read(&self) -> Result<ExpliceConfig> {
@@ -71,3 +309,55 @@ where
             .with_context(|| format!("no config found, run \"{} config init\" first", APP_NAME))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_api_key_resolution_order() {
+        let keyring = || Ok(Some("keyring-key".to_owned()));
+        let empty_keyring = || Ok(None);
+        let unused_keyring = || -> Result<Option<String>> { panic!("keyring was read") };
+
+        let mut config = ExpliceConfig::new("file-key".to_owned(), 1000);
+        let api_key = config.resolve_api_key(None, "sh", unused_keyring);
+        assert_eq!(api_key.unwrap(), "file-key");
+
+        // keyring takes precedence over the file even when it has no key
+        config.api_key_keyring = true;
+        assert_eq!(
+            config.resolve_api_key(None, "sh", keyring).unwrap(),
+            "keyring-key"
+        );
+        assert!(config.resolve_api_key(None, "sh", empty_keyring).is_err());
+
+        config.api_key_cmd = Some("echo cmd-key".to_owned());
+        let api_key = config.resolve_api_key(None, "sh", unused_keyring);
+        assert_eq!(api_key.unwrap(), "cmd-key");
+
+        let env_api_key = Some("env-key".to_owned());
+        let api_key = config.resolve_api_key(env_api_key, "sh", unused_keyring);
+        assert_eq!(api_key.unwrap(), "env-key");
+    }
+
+    #[test]
+    fn test_update_moves_key_to_keyring() -> Result<()> {
+        let mut config = ExpliceConfig::new("file-key".to_owned(), 1000);
+        let change = config.update(ExpliceConfigUpdate {
+            api_key_keyring: Some(true),
+            ..Default::default()
+        })?;
+
+        assert_eq!(change, Some(KeyringChange::Store("file-key".to_owned())));
+        assert!(config.api_key.is_empty());
+
+        let change = config.update(ExpliceConfigUpdate {
+            api_key: Some("new-key".to_owned()),
+            ..Default::default()
+        })?;
+        assert_eq!(change, Some(KeyringChange::Store("new-key".to_owned())));
+        assert!(config.api_key.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,289 @@
+use crate::{ChatMessage, ChatRecord, Role, SessionStatus};
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::json;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Markdown,
+    Html,
+    Json,
+    /// OpenAI chat fine-tuning format, one conversation per line
+    FineTune,
+}
+
+#[derive(Serialize)]
+struct ExportedRecord<'r> {
+    id: &'r str,
+    #[serde(flatten)]
+    record: &'r ChatRecord,
+}
+
+/// Renders records with their ids in one document, or one line per record for fine-tuning
+pub fn export_records(records: &[(String, ChatRecord)], format: ExportFormat) -> Result<String> {
+    let export = match format {
+        ExportFormat::Markdown => records
+            .iter()
+            .map(|(id, record)| markdown(id, record))
+            .collect::<Vec<_>>()
+            .join("\n---\n\n"),
+        ExportFormat::Html => html(records),
+        ExportFormat::Json => {
+            let records: Vec<_> = records
+                .iter()
+                .map(|(id, record)| ExportedRecord { id, record })
+                .collect();
+            serde_json::to_string_pretty(&records)?
+        }
+        ExportFormat::FineTune => records
+            .iter()
+            .filter_map(|(_, record)| fine_tune_line(record).transpose())
+            .collect::<Result<Vec<_>>>()?
+            .join("\n"),
+    };
+
+    Ok(export)
+}
+
+fn markdown(id: &str, record: &ChatRecord) -> String {
+    let mut markdown = format!("# {}\n\n", heading(record));
+    markdown.push_str(&format!("- id: `{id}`\n"));
+    markdown.push_str(&format!("- date: {}\n", record.creation_date()));
+    if let Some(model) = record.model() {
+        markdown.push_str(&format!("- model: {model}\n"));
+    }
+    markdown.push_str(&format!("- status: {}\n", record.status()));
+
+    if let Some(system_prompt) = record.system_prompt().filter(|system| !system.is_empty()) {
+        markdown.push_str(&format!("\n## System\n\n{system_prompt}\n"));
+    }
+    for message in record.messages() {
+        markdown.push_str(&format!(
+            "\n## {}\n\n{}\n",
+            role_title(message.role()),
+            message.content()
+        ));
+        if let Some(details) = message_details(message) {
+            markdown.push_str(&format!("\n*{details}*\n"));
+        }
+    }
+
+    markdown
+}
+
+fn html(records: &[(String, ChatRecord)]) -> String {
+    let mut html = String::from(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Conversations</title>
+<style>
+body { font-family: sans-serif; max-width: 50rem; margin: 2rem auto; padding: 0 1rem; color: #222; }
+section { margin-bottom: 3rem; }
+.meta, .details { color: #666; font-size: 0.85rem; }
+.message { border-radius: 0.5rem; padding: 0.5rem 1rem; margin: 1rem 0; }
+.system { background: #f3f3f3; }
+.user { background: #e8f0fe; }
+.assistant { background: #eef8ee; }
+.role { font-weight: bold; }
+.content { white-space: pre-wrap; font-family: inherit; margin: 0.5rem 0; }
+</style>
+</head>
+<body>
+"#,
+    );
+
+    for (id, record) in records {
+        html.push_str(&format!(
+            "<section>\n<h2>{}</h2>\n",
+            escape_html(&heading(record))
+        ));
+        let model = record
+            .model()
+            .map(|model| format!(", {}", escape_html(model)))
+            .unwrap_or_default();
+        html.push_str(&format!(
+            "<p class=\"meta\">{} &middot; {}{model} &middot; {}</p>\n",
+            escape_html(id),
+            record.creation_date(),
+            record.status()
+        ));
+
+        if let Some(system_prompt) = record.system_prompt().filter(|system| !system.is_empty()) {
+            html_message(&mut html, "system", "System", system_prompt, None);
+        }
+        for message in record.messages() {
+            let class = match message.role() {
+                Role::User => "user",
+                Role::Assistant => "assistant",
+            };
+            html_message(
+                &mut html,
+                class,
+                role_title(message.role()),
+                message.content(),
+                message_details(message),
+            );
+        }
+        html.push_str("</section>\n");
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+fn html_message(
+    html: &mut String,
+    class: &str,
+    title: &str,
+    content: &str,
+    details: Option<String>,
+) {
+    html.push_str(&format!("<div class=\"message {class}\">\n<div class=\"role\">{title}</div>\n<pre class=\"content\">{}</pre>\n",
+        escape_html(content)));
+    if let Some(details) = details {
+        html.push_str(&format!(
+            "<div class=\"details\">{}</div>\n",
+            escape_html(&details)
+        ));
+    }
+    html.push_str("</div>\n");
+}
+
+/// Conversation up to its last answer as fine-tuning example, `None` for records without
+/// any answer, sessions still in progress and ones that changed their system prompt
+fn fine_tune_line(record: &ChatRecord) -> Result<Option<String>> {
+    if record.status() == SessionStatus::Active || record.system_prompt_changed() {
+        return Ok(None);
+    }
+    let Some(last_answer) = record
+        .messages()
+        .iter()
+        .rposition(|message| message.role() == Role::Assistant)
+    else {
+        return Ok(None);
+    };
+
+    let system = record
+        .system_prompt()
+        .filter(|system| !system.is_empty())
+        .map(|system| json!({"role": "system", "content": system}));
+    let messages: Vec<_> = system
+        .into_iter()
+        .chain(record.messages()[..=last_answer].iter().map(|message| {
+            let role = match message.role() {
+                Role::User => "user",
+                Role::Assistant => "assistant",
+            };
+            json!({"role": role, "content": message.content()})
+        }))
+        .collect();
+
+    Ok(Some(serde_json::to_string(
+        &json!({ "messages": messages }),
+    )?))
+}
+
+fn heading(record: &ChatRecord) -> String {
+    match record.title() {
+        Some(title) => title.to_owned(),
+        None => format!(
+            "Conversation with assistant \"{}\"",
+            record.assistant_name()
+        ),
+    }
+}
+
+fn role_title(role: Role) -> &'static str {
+    match role {
+        Role::User => "User",
+        Role::Assistant => "Assistant",
+    }
+}
+
+/// Model, usage and latency of answer, typed prompt of user message with placeholders
+fn message_details(message: &ChatMessage) -> Option<String> {
+    if let Some(original_content) = message.original_content() {
+        return Some(format!("typed as: {original_content}"));
+    }
+
+    let generation = message.generation()?;
+    let mut details = vec![];
+    if let Some(model) = &generation.model {
+        details.push(model.to_owned());
+    }
+    if let Some(usage) = &generation.usage {
+        details.push(format!(
+            "{} prompt and {} completion tokens",
+            usage.prompt_tokens, usage.completion_tokens
+        ));
+    }
+    details.push(format!("{} ms", generation.latency_ms));
+    Some(details.join(", "))
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record() -> ChatRecord {
+        let mut record = ChatRecord::new("default")
+            .with_model("gpt-4o")
+            .with_system_prompt("Be brief")
+            .with_status(SessionStatus::Finished);
+        record.add_message(ChatMessage::new_user("What is <b>?"));
+        record.add_message(ChatMessage::new_assistant("Bold tag"));
+        record
+    }
+
+    #[test]
+    fn test_export_records() -> Result<()> {
+        let records = vec![
+            ("01A".to_owned(), record()),
+            ("01B".to_owned(), ChatRecord::new("default")),
+        ];
+
+        let markdown = export_records(&records, ExportFormat::Markdown)?;
+        assert!(markdown.contains("## System\n\nBe brief\n"));
+        assert!(markdown.contains("## User\n\nWhat is <b>?\n"));
+
+        let html = export_records(&records, ExportFormat::Html)?;
+        assert!(html.contains("What is &lt;b&gt;?"));
+        assert!(html.ends_with("</html>\n"));
+
+        let json: serde_json::Value =
+            serde_json::from_str(&export_records(&records, ExportFormat::Json)?)?;
+        assert_eq!(json[0]["id"], "01A");
+        assert_eq!(json[0]["messages"][1]["content"], "Bold tag");
+
+        let fine_tune = export_records(&records, ExportFormat::FineTune)?;
+        assert_eq!(fine_tune.lines().count(), 1);
+        let line: serde_json::Value = serde_json::from_str(&fine_tune)?;
+        assert_eq!(line["messages"][0]["role"], "system");
+        assert_eq!(line["messages"][2]["role"], "assistant");
+        Ok(())
+    }
+
+    #[test]
+    fn test_fine_tune_line() -> Result<()> {
+        let mut unanswered = record();
+        unanswered.add_message(ChatMessage::new_user("And <i>?"));
+        let line: serde_json::Value = serde_json::from_str(&fine_tune_line(&unanswered)?.unwrap())?;
+        assert_eq!(line["messages"].as_array().map(Vec::len), Some(3));
+        assert_eq!(line["messages"][2]["content"], "Bold tag");
+
+        let active = record().with_status(SessionStatus::Active);
+        assert!(fine_tune_line(&active)?.is_none());
+        let changed = record().with_system_prompt_changed(true);
+        assert!(fine_tune_line(&changed)?.is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,318 @@
+use crate::{parse_code_blocks, parse_unified_diff};
+use anyhow::{bail, Context, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const SEARCH_MARKER: &str = "<<<<<<< SEARCH";
+const DIVIDER_MARKER: &str = "=======";
+const REPLACE_MARKER: &str = ">>>>>>> REPLACE";
+
+/// File content before and after the edit, `None` when file does not exist
+#[derive(Debug)]
+pub struct FileEdit {
+    pub path: String,
+    pub original: Option<String>,
+    pub edited: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+struct SearchReplace {
+    path: String,
+    search: String,
+    replace: String,
+}
+
+impl SearchReplace {
+    fn apply(&self, content: &str) -> Result<String> {
+        if self.search.is_empty() {
+            return match content.is_empty() {
+                true => Ok(self.replace.to_owned()),
+                false => Ok(format!("{content}{}", self.replace)),
+            };
+        }
+
+        match content.matches(&self.search).count() {
+            0 => bail!("search block does not match content of {}", self.path),
+            1 => Ok(content.replacen(&self.search, &self.replace, 1)),
+            _ => bail!("search block matches {} more than once", self.path),
+        }
+    }
+}
+
+/// Collects unified diffs and search/replace blocks from the completion and validates them
+/// against current file contents, edits are allowed only for the given paths
+pub fn propose_file_edits(completion: &str, allowed_paths: &[String]) -> Result<Vec<FileEdit>> {
+    let mut edits: BTreeMap<String, FileEdit> = BTreeMap::new();
+
+    for block in parse_code_blocks(completion) {
+        if !block.is_diff() {
+            continue;
+        }
+        for patch in parse_unified_diff(&block.code)? {
+            let edit = file_edit(&mut edits, patch.path(), allowed_paths)?;
+            let content = edit.edited.as_deref().unwrap_or_default();
+            edit.edited = match patch.is_deleted_file() {
+                true => None,
+                false => Some(patch.apply(content)?),
+            };
+        }
+    }
+
+    for search_replace in parse_search_replace_blocks(completion)? {
+        let edit = file_edit(&mut edits, &search_replace.path, allowed_paths)?;
+        let content = edit.edited.as_deref().unwrap_or_default();
+        edit.edited = Some(search_replace.apply(content)?);
+    }
+
+    Ok(edits
+        .into_values()
+        .filter(|edit| edit.original != edit.edited)
+        .collect())
+}
+
+fn file_edit<'e>(
+    edits: &'e mut BTreeMap<String, FileEdit>,
+    path: &str,
+    allowed_paths: &[String],
+) -> Result<&'e mut FileEdit> {
+    let path = normalize_path(path);
+    if !allowed_paths
+        .iter()
+        .any(|allowed| normalize_path(allowed) == path)
+    {
+        bail!("model tried to edit {path}, which was not referenced in the prompt");
+    }
+
+    if !edits.contains_key(&path) {
+        let original = match Path::new(&path).exists() {
+            true => {
+                Some(fs::read_to_string(&path).with_context(|| format!("failed to read {path}"))?)
+            }
+            false => None,
+        };
+        edits.insert(
+            path.to_owned(),
+            FileEdit {
+                path: path.to_owned(),
+                edited: original.clone(),
+                original,
+            },
+        );
+    }
+
+    Ok(edits.get_mut(&path).unwrap())
+}
+
+fn normalize_path(path: &str) -> String {
+    path.trim()
+        .trim_matches('`')
+        .trim_start_matches("./")
+        .replace('\\', "/")
+}
+
+/// Parses blocks in the format:
+/// ```text
+/// path/to/file.rs
+/// <<<<<<< SEARCH
+/// old lines
+/// =======
+/// new lines
+/// >>>>>>> REPLACE
+/// ```
+fn parse_search_replace_blocks(text: &str) -> Result<Vec<SearchReplace>> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut blocks = vec![];
+    let mut index = 0;
+
+    while index < lines.len() {
+        if lines[index].trim() != SEARCH_MARKER {
+            index += 1;
+            continue;
+        }
+
+        let path = lines[..index]
+            .iter()
+            .rev()
+            .map(|line| line.trim())
+            .find(|line| !line.is_empty() && !line.starts_with("```"))
+            .filter(|line| ![SEARCH_MARKER, DIVIDER_MARKER, REPLACE_MARKER].contains(line))
+            .with_context(|| {
+                format!(
+                    "search/replace block on line {} has no file path",
+                    index + 1
+                )
+            })?;
+
+        let end = find_marker(&lines, index + 1, DIVIDER_MARKER).and_then(|divider| {
+            find_marker(&lines, divider + 1, REPLACE_MARKER).map(|end| (divider, end))
+        });
+        let Some((divider, end)) = end else {
+            bail!("search/replace block of {path} is not closed");
+        };
+
+        blocks.push(SearchReplace {
+            path: normalize_path(path),
+            search: join_lines(&lines[index + 1..divider]),
+            replace: join_lines(&lines[divider + 1..end]),
+        });
+        index = end + 1;
+    }
+
+    Ok(blocks)
+}
+
+fn find_marker(lines: &[&str], from: usize, marker: &str) -> Option<usize> {
+    (from..lines.len()).find(|index| lines[*index].trim() == marker)
+}
+
+fn join_lines(lines: &[&str]) -> String {
+    lines.iter().map(|line| format!("{line}\n")).collect()
+}
+
+/// Original contents of edited files, used to revert applied edits
+pub struct EditBackup {
+    dir: PathBuf,
+    files: Vec<(String, Option<String>)>,
+}
+
+impl EditBackup {
+    /// Stores original contents of all files in the backup directory before writing any edit,
+    /// edits written before a failed one are reverted
+    pub fn apply(edits: &[FileEdit], backup_dir: PathBuf) -> Result<Self> {
+        fs::create_dir_all(&backup_dir)?;
+        for (index, edit) in edits.iter().enumerate() {
+            if let Some(original) = &edit.original {
+                let file_name = Path::new(&edit.path)
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .unwrap_or_default();
+                fs::write(backup_dir.join(format!("{index}-{file_name}")), original)?;
+            }
+        }
+        let files: Vec<_> = edits
+            .iter()
+            .map(|edit| (edit.path.to_owned(), edit.original.clone()))
+            .collect();
+
+        for (index, edit) in edits.iter().enumerate() {
+            let Err(err) = write_edit(edit) else {
+                continue;
+            };
+            // failed write may have left the file half written, so it's restored too
+            let err = err.context(format!("failed to edit {}", edit.path));
+            return match revert_files(&files[..=index]) {
+                Ok(()) => Err(err.context("earlier edits were reverted")),
+                Err(revert_err) => Err(err.context(format!(
+                    "reverting earlier edits failed too, originals are in {backup_dir:?}: {revert_err:#}"
+                ))),
+            };
+        }
+
+        Ok(Self {
+            dir: backup_dir,
+            files,
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Restores original contents, files created by the edit are removed
+    pub fn revert(&self) -> Result<Vec<String>> {
+        revert_files(&self.files)?;
+
+        Ok(self.files.iter().map(|(path, _)| path.to_owned()).collect())
+    }
+}
+
+fn write_edit(edit: &FileEdit) -> Result<()> {
+    match &edit.edited {
+        None => fs::remove_file(&edit.path)?,
+        Some(edited) => {
+            if let Some(dir) = Path::new(&edit.path).parent() {
+                fs::create_dir_all(dir)?;
+            }
+            fs::write(&edit.path, edited)?;
+        }
+    }
+    Ok(())
+}
+
+fn revert_files(files: &[(String, Option<String>)]) -> Result<()> {
+    for (path, original) in files {
+        match original {
+            None if Path::new(path).is_file() => fs::remove_file(path)?,
+            None => {}
+            Some(original) => fs::write(path, original)?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_search_replace_blocks() {
+        let text = "Change it:\n```rust\nsrc/main.rs\n<<<<<<< SEARCH\nlet a = 1;\n=======\nlet a = 2;\n>>>>>>> REPLACE\n```";
+        let blocks = parse_search_replace_blocks(text).unwrap();
+
+        assert_eq!(
+            blocks,
+            vec![SearchReplace {
+                path: "src/main.rs".to_owned(),
+                search: "let a = 1;\n".to_owned(),
+                replace: "let a = 2;\n".to_owned(),
+            }]
+        );
+        assert_eq!(
+            blocks[0].apply("fn main() {\nlet a = 1;\n}\n").unwrap(),
+            "fn main() {\nlet a = 2;\n}\n"
+        );
+        assert!(blocks[0].apply("let a = 1;\nlet a = 1;\n").is_err());
+        assert!(blocks[0].apply("let b = 1;\n").is_err());
+    }
+
+    #[test]
+    fn test_reject_malformed_search_replace_blocks() {
+        let missing_path = "src/main.rs\n<<<<<<< SEARCH\na\n=======\nb\n>>>>>>> REPLACE\n\n<<<<<<< SEARCH\nc\n=======\nd\n>>>>>>> REPLACE";
+        assert!(parse_search_replace_blocks(missing_path).is_err());
+
+        let not_closed = "src/main.rs\n<<<<<<< SEARCH\na\n=======\nb";
+        assert!(parse_search_replace_blocks(not_closed).is_err());
+    }
+
+    #[test]
+    fn test_revert_edits_when_write_fails() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("explice-edit-test-{}", ulid::Ulid::new()));
+        fs::create_dir_all(dir.join("directory"))?;
+        let file = dir.join("file.txt").to_string_lossy().to_string();
+        fs::write(&file, "original")?;
+
+        let edits = [
+            FileEdit {
+                path: file.to_owned(),
+                original: Some("original".to_owned()),
+                edited: Some("edited".to_owned()),
+            },
+            FileEdit {
+                path: dir.join("directory").to_string_lossy().to_string(),
+                original: None,
+                edited: Some("not a file".to_owned()),
+            },
+        ];
+
+        assert!(EditBackup::apply(&edits, dir.join("backup")).is_err());
+        assert_eq!(fs::read_to_string(&file)?, "original");
+        assert_eq!(
+            fs::read_to_string(dir.join("backup").join("0-file.txt"))?,
+            "original"
+        );
+
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,193 @@
+use anyhow::{bail, Context, Result};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+const FILE_DIFF_PREFIX: &str = "diff --git ";
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Changes staged for the next commit
+pub fn staged_diff() -> Result<String> {
+    git(&["diff", "--cached", "--no-color", "--no-ext-diff"])
+}
+
+/// Changes in revision range, or uncommitted changes against `HEAD` when there is no range
+pub fn diff(rev_range: Option<&str>, context_lines: u32) -> Result<String> {
+    let context = format!("-U{context_lines}");
+    git(&[
+        "diff",
+        "--no-color",
+        "--no-ext-diff",
+        &context,
+        rev_range.unwrap_or("HEAD"),
+    ])
+}
+
+/// Whether argument names a revision or revision range rather than a path
+pub fn is_revision(rev: &str) -> bool {
+    if rev.contains("..") {
+        return true;
+    }
+
+    Command::new("git")
+        .args([
+            "rev-parse",
+            "--verify",
+            "--quiet",
+            &format!("{rev}^{{commit}}"),
+        ])
+        .stdout(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// Changes of the current branch since it diverged from `base`
+pub fn branch_diff(base: &str) -> Result<String> {
+    git(&[
+        "diff",
+        "--no-color",
+        "--no-ext-diff",
+        &format!("{base}...HEAD"),
+    ])
+}
+
+/// Commit messages of the current branch since it diverged from `base`
+pub fn branch_log(base: &str) -> Result<String> {
+    git(&[
+        "log",
+        "--no-color",
+        "--format=- %s%n%b",
+        &format!("{base}..HEAD"),
+    ])
+}
+
+/// Commits staged changes with given message, git output goes straight to terminal
+pub fn commit(message: &str) -> Result<()> {
+    let mut child = Command::new("git")
+        .args(["commit", "-F", "-"])
+        .stdin(Stdio::piped())
+        .spawn()
+        .context("failed to run git")?;
+
+    child
+        .stdin
+        .take()
+        .context("failed to pass commit message to git")?
+        .write_all(message.as_bytes())?;
+
+    if !child.wait()?.success() {
+        bail!("git commit failed");
+    }
+    Ok(())
+}
+
+/// Rough token count, good enough to keep prompts under the model context
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(CHARS_PER_TOKEN)
+}
+
+/// Splits diff into chunks of whole files fitting in `max_tokens`, too large file diffs are truncated
+pub fn chunk_diff(diff: &str, max_tokens: usize) -> Vec<String> {
+    let max_chars = max_tokens * CHARS_PER_TOKEN;
+    let mut chunks = vec![];
+    let mut chunk = String::new();
+
+    for file_diff in split_file_diffs(diff) {
+        let file_diff = truncate_file_diff(file_diff, max_chars);
+        if !chunk.is_empty() && chunk.chars().count() + file_diff.chars().count() > max_chars {
+            chunks.push(std::mem::take(&mut chunk));
+        }
+        chunk.push_str(&file_diff);
+    }
+
+    if !chunk.is_empty() {
+        chunks.push(chunk);
+    }
+    chunks
+}
+
+fn split_file_diffs(diff: &str) -> Vec<&str> {
+    let mut starts: Vec<usize> = diff
+        .match_indices(FILE_DIFF_PREFIX)
+        .map(|(index, _)| index)
+        .filter(|index| *index == 0 || diff[..*index].ends_with('\n'))
+        .collect();
+    if starts.first() != Some(&0) {
+        starts.insert(0, 0);
+    }
+
+    starts
+        .iter()
+        .zip(starts.iter().skip(1).chain([&diff.len()]))
+        .map(|(start, end)| &diff[*start..*end])
+        .filter(|file_diff| !file_diff.trim().is_empty())
+        .collect()
+}
+
+fn truncate_file_diff(file_diff: &str, max_chars: usize) -> String {
+    if file_diff.chars().count() <= max_chars {
+        return file_diff.to_owned();
+    }
+
+    let mut truncated = String::new();
+    let mut lines = file_diff.lines();
+    for line in lines.by_ref() {
+        if truncated.chars().count() + line.chars().count() + 1 > max_chars {
+            break;
+        }
+        truncated.push_str(line);
+        truncated.push('\n');
+    }
+
+    let remaining = lines.count() + 1;
+    truncated.push_str(&format!(
+        "... {remaining} more lines of this file truncated\n"
+    ));
+    truncated
+}
+
+fn git(args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .args(args)
+        .output()
+        .context("failed to run git, is it installed?")?;
+
+    if !output.status.success() {
+        bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file_diff(name: &str, lines: usize) -> String {
+        let changes: String = (0..lines).map(|i| format!("+line {i}\n")).collect();
+        format!("diff --git a/{name} b/{name}\n--- a/{name}\n+++ b/{name}\n@@ -0,0 +1,{lines} @@\n{changes}")
+    }
+
+    #[test]
+    fn test_chunk_diff() {
+        let diff = [
+            file_diff("a.rs", 10),
+            file_diff("b.rs", 10),
+            file_diff("c.rs", 200),
+        ]
+        .concat();
+
+        let chunks = chunk_diff(&diff, 100);
+
+        assert_eq!(chunks.len(), 2);
+        assert!(chunks[0].contains("a/a.rs") && chunks[0].contains("a/b.rs"));
+        assert!(chunks[1].contains("a/c.rs"));
+        assert!(chunks[1].ends_with("more lines of this file truncated\n"));
+        assert!(chunks.iter().all(|chunk| estimate_tokens(chunk) <= 110));
+
+        assert_eq!(chunk_diff(&file_diff("a.rs", 1), 100).len(), 1);
+        assert!(chunk_diff("", 100).is_empty());
+    }
+}
@@ -1,20 +1,46 @@
-#![feature(absolute_path)]
-
 mod assistants;
+mod chat_command;
 mod chat_record;
+mod chatgpt;
+mod code_block;
 mod completion;
+mod completion_provider;
 mod config;
+mod export;
+mod file_edit;
+mod git;
 mod open_ai;
+mod patch;
 mod placeholder;
+mod prompt_history;
+mod review;
+mod secrets;
+mod shell_command;
+mod shell_environment;
 mod storage;
+mod usage;
 pub mod validation;
 
 pub use assistants::*;
+pub use chat_command::*;
 pub use chat_record::*;
+pub use chatgpt::*;
+pub use code_block::*;
 pub use completion::*;
+pub use completion_provider::*;
 pub use config::*;
+pub use export::*;
+pub use file_edit::*;
+pub use git::*;
 pub use open_ai::*;
+pub use patch::*;
 pub use placeholder::*;
+pub use prompt_history::*;
+pub use review::*;
+pub use secrets::*;
+pub use shell_command::*;
+pub use shell_environment::*;
 pub use storage::{KVStorage, Storage};
+pub use usage::*;
 
 pub const APP_NAME: &str = "explice";
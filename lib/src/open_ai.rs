@@ -1,5 +1,6 @@
 mod assistants;
 mod chat;
+mod interrupt;
 mod thread;
 
 use anyhow::Result;
@@ -7,8 +8,7 @@ use assistants::OpenAiAssistants;
 pub use assistants::OpenAiChatAssistant;
 use async_openai::config::OpenAIConfig;
 use async_openai::Client;
-use chat::Chat;
-pub use chat::ChatController;
+pub use chat::{Chat, ChatController};
 
 pub struct OpenAi {
     client: Client<OpenAIConfig>,
@@ -22,14 +22,14 @@ impl OpenAi {
         Self { client }
     }
 
-    pub fn chat<C>(&self, controller: C) -> Chat<C>
+    pub fn chat<C>(&self, controller: C) -> Chat<'_, C>
     where
         C: ChatController,
     {
         Chat::new(&self.client, controller)
     }
 
-    pub fn assistants(&self) -> OpenAiAssistants {
+    pub fn assistants(&self) -> OpenAiAssistants<'_> {
         OpenAiAssistants::new(&self.client)
     }
 
@@ -4,7 +4,7 @@ use async_openai::types::AssistantObject;
 use async_openai::Client;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OpenAiChatAssistant {
     id: String,
     name: Option<String>,
@@ -1,18 +1,26 @@
-use crate::chat_record::ChatMessage;
+use crate::chat_command::chat_commands_help;
+use crate::open_ai::interrupt::interruptible;
 use crate::open_ai::thread::Thread;
-use crate::{replace_placeholders, ChatAssistant, ChatRecord, ExpliceConfig, OpenAiChatAssistant};
-use anyhow::Context;
+use crate::{
+    replace_placeholders, ChatAssistant, ChatCommand, ChatMessage, ChatRecord, ChatRecorder,
+    ExpliceConfig, Generation, OpenAiChatAssistant, Role, SessionStatus, TokenUsage, UsageTracker,
+};
+use anyhow::{bail, Context};
 use async_openai::config::OpenAIConfig;
 use async_openai::types::{
     ChatCompletionRequestAssistantMessageArgs, ChatCompletionRequestMessage,
     ChatCompletionRequestSystemMessageArgs, ChatCompletionRequestUserMessageArgs,
-    ChatCompletionRequestUserMessageContent, CreateChatCompletionRequestArgs,
+    CreateChatCompletionRequestArgs,
 };
 use async_openai::Client;
+use chrono::{DateTime, Local};
+use std::time::Instant;
+use std::{fs, io};
 
 pub trait ChatController {
     fn create_prompt(&mut self) -> anyhow::Result<Option<String>>;
     fn on_completion(&self, completion: &str) -> anyhow::Result<()>;
+    fn on_command_output(&self, output: &str) -> anyhow::Result<()>;
 }
 
 pub struct Chat<'c, C>
@@ -21,6 +29,11 @@ where
 {
     client: &'c Client<OpenAIConfig>,
     controller: C,
+    assistants: Vec<ChatAssistant>,
+    recorder: Option<Box<dyn ChatRecorder>>,
+    resumed: Option<ChatRecord>,
+    usage_tracker: Option<Box<dyn UsageTracker>>,
+    usage: TokenUsage,
 }
 
 impl<'c, C> Chat<'c, C>
@@ -31,69 +44,363 @@ where
         Self {
             client: open_ai_client,
             controller,
+            assistants: vec![],
+            recorder: None,
+            resumed: None,
+            usage_tracker: None,
+            usage: TokenUsage::default(),
         }
     }
 
+    /// Assistants available to switch to with the `/assistant` command
+    pub fn with_assistants(mut self, assistants: Vec<ChatAssistant>) -> Self {
+        self.assistants = assistants;
+        self
+    }
+
+    /// Continues earlier conversation with its messages and system prompt, saved as a new record
+    pub fn with_resumed(mut self, record: ChatRecord) -> Self {
+        self.resumed = Some(record);
+        self
+    }
+
+    /// Recorder saving the session at its start and after every turn
+    pub fn with_recorder(mut self, recorder: impl ChatRecorder + 'static) -> Self {
+        self.recorder = Some(Box::new(recorder));
+        self
+    }
+
+    /// Tracker storing token usage of completions and refusing them over hard budgets
+    pub fn with_usage_tracker(mut self, usage_tracker: impl UsageTracker + 'static) -> Self {
+        self.usage_tracker = Some(Box::new(usage_tracker));
+        self
+    }
+
+    /// Runs the session until the prompt is empty or interrupted with Ctrl-C,
+    /// which during a completion ends the session instead of the process
     pub async fn create_loop(
         &mut self,
         config: &ExpliceConfig,
         assistant: &ChatAssistant,
     ) -> anyhow::Result<ChatRecord> {
-        let mut message_builder = ChatMessagesBuilder::new(assistant.system())?;
+        let mut session = ChatSession::new(assistant);
+        if let Some(record) = self.resumed.take() {
+            let output = format!(
+                "Continuing conversation of {} messages",
+                record.messages().len()
+            );
+            session.resume(record);
+            self.controller.on_command_output(&output)?;
+        }
+        self.record(&session.to_chat_record(SessionStatus::Active))?;
+
+        let result = self.run_loop(config, &mut session).await;
+        self.finish_record(session.to_chat_record(SessionStatus::Active), result)
+    }
+
+    async fn run_loop(
+        &mut self,
+        config: &ExpliceConfig,
+        session: &mut ChatSession,
+    ) -> anyhow::Result<SessionStatus> {
         loop {
-            let prompt = match self.controller.create_prompt()? {
-                None => break,
-                Some(prompt) => replace_placeholders(prompt)?,
+            let Some(input) = self.next_prompt()? else {
+                break;
             };
-            message_builder.add_user(&prompt)?;
 
-            let completion = self
-                .chat_completion(config.token_limit(), assistant, message_builder.build())
-                .await?;
-            message_builder.add_assistant(&completion)?;
+            match ChatCommand::parse(&input) {
+                Ok(None) => {}
+                Ok(Some(command)) => {
+                    if let Err(err) = self.run_command(config, session, command).await {
+                        self.controller.on_command_output(&format!("{err:#}"))?;
+                    }
+                    continue;
+                }
+                Err(err) => {
+                    self.controller.on_command_output(&format!("{err:#}"))?;
+                    continue;
+                }
+            }
 
-            self.controller.on_completion(&completion)?;
+            let prompt = replace_placeholders(input.to_owned())?;
+            session
+                .messages
+                .add(ChatMessage::new_user(&prompt).with_original_content(&input));
+            self.record_turn(&session.to_chat_record(SessionStatus::Active))?;
+
+            let Some(completion) = self
+                .interruptible_completion(
+                    &session.assistant_name,
+                    session.token_limit.unwrap_or(*config.token_limit()),
+                    &session.model,
+                    session.messages.build()?,
+                )
+                .await?
+            else {
+                return Ok(SessionStatus::Interrupted);
+            };
+            session.messages.add(completion.clone());
+            self.record_turn(&session.to_chat_record(SessionStatus::Active))?;
+
+            self.controller.on_completion(completion.content())?;
         }
 
-        Ok(message_builder.to_chat_record(assistant.name()))
+        Ok(SessionStatus::Finished)
+    }
+
+    async fn run_command(
+        &mut self,
+        config: &ExpliceConfig,
+        session: &mut ChatSession,
+        command: ChatCommand,
+    ) -> anyhow::Result<()> {
+        let output = match command {
+            ChatCommand::Assistant(name) => {
+                let assistant = self
+                    .assistants
+                    .iter()
+                    .find(|a| a.name() == name)
+                    .with_context(|| format!("assistant \"{name}\" not found"))?;
+                session.switch_assistant(assistant);
+                format!("Switched to assistant \"{name}\"")
+            }
+            ChatCommand::Model(model) => {
+                let output = format!("Switched model to {model}");
+                session.model = model;
+                output
+            }
+            ChatCommand::System(None) => session.messages.system().to_owned(),
+            ChatCommand::System(Some(system)) => {
+                session.messages.set_system(&system);
+                "Replaced system prompt".to_owned()
+            }
+            ChatCommand::Clear => {
+                session.messages.clear();
+                "Cleared session messages".to_owned()
+            }
+            ChatCommand::Undo => match session.messages.undo() {
+                true => "Removed the last prompt and answer".to_owned(),
+                false => bail!("nothing to undo"),
+            },
+            ChatCommand::Retry => {
+                if !session.messages.ends_with_assistant() {
+                    bail!("nothing to retry");
+                }
+                // previous answer stays until the new one arrives
+                let mut messages = session.messages.build()?;
+                messages.pop();
+                let completion = self
+                    .interruptible_completion(
+                        &session.assistant_name,
+                        session.token_limit.unwrap_or(*config.token_limit()),
+                        &session.model,
+                        messages,
+                    )
+                    .await?
+                    .context("completion was interrupted")?;
+                session.messages.replace_last_assistant(completion.clone());
+                self.record_turn(&session.to_chat_record(SessionStatus::Active))?;
+                return self.controller.on_completion(completion.content());
+            }
+            ChatCommand::Save(path) => {
+                let record = session.to_chat_record(SessionStatus::Active);
+                fs::write(&path, record.to_string())
+                    .with_context(|| format!("failed to save transcript to {path}"))?;
+                format!("Saved transcript to {path}")
+            }
+            ChatCommand::Usage => self.usage.to_string(),
+            ChatCommand::Help => chat_commands_help(),
+        };
+
+        self.controller.on_command_output(&output)
+    }
+
+    /// Single completion outside of the chat loop, for commands generating one answer from input
+    pub async fn complete(
+        &mut self,
+        config: &ExpliceConfig,
+        assistant: &ChatAssistant,
+        prompt: &str,
+    ) -> anyhow::Result<String> {
+        let mut messages = ChatMessagesBuilder::new(assistant.system());
+        messages.add(ChatMessage::new_user(prompt));
+
+        let completion = self
+            .chat_completion(
+                assistant.name(),
+                assistant.token_limit().unwrap_or(*config.token_limit()),
+                assistant.model(),
+                messages.build()?,
+            )
+            .await?;
+        Ok(completion.content().to_owned())
     }
 
     pub async fn create_loop_with_thread(
         &mut self,
         assistant: &OpenAiChatAssistant,
     ) -> anyhow::Result<ChatRecord> {
-        let mut chat_record = ChatRecord::new(assistant.name());
-        let thread = Thread::new(&self.client).await?;
+        let mut chat_record = ChatRecord::new(assistant.name())
+            .with_model(assistant.model())
+            .with_system_prompt(assistant.system());
+        self.record(&chat_record)?;
+
+        let result = self.run_thread_loop(assistant, &mut chat_record).await;
+        self.finish_record(chat_record, result)
+    }
+
+    async fn run_thread_loop(
+        &mut self,
+        assistant: &OpenAiChatAssistant,
+        chat_record: &mut ChatRecord,
+    ) -> anyhow::Result<SessionStatus> {
+        let thread = Thread::new(self.client).await?;
 
         loop {
-            let prompt = match self.controller.create_prompt()? {
-                None => break,
-                Some(prompt) => replace_placeholders(prompt)?,
+            let Some(input) = self.next_prompt()? else {
+                break;
             };
-            chat_record.add_user(&prompt);
+            if !matches!(ChatCommand::parse(&input), Ok(None)) {
+                self.controller
+                    .on_command_output("chat commands are not supported in threads")?;
+                continue;
+            }
+
+            let prompt = replace_placeholders(input.to_owned())?;
+            chat_record.add_message(ChatMessage::new_user(&prompt).with_original_content(&input));
+            self.record_turn(chat_record)?;
 
-            let completion = thread.chat_completion(&prompt, &assistant.id()).await?;
-            chat_record.add_assistant(&completion);
+            self.check_budgets()?;
+            let start = Instant::now();
+            let Some(completion) =
+                interruptible(thread.chat_completion(&prompt, assistant.id())).await
+            else {
+                return Ok(SessionStatus::Interrupted);
+            };
+            let completion = completion?;
+            let generation = Generation {
+                latency_ms: start.elapsed().as_millis() as u64,
+                ..Default::default()
+            };
+            chat_record
+                .add_message(ChatMessage::new_assistant(&completion).with_generation(generation));
+            self.record_turn(chat_record)?;
 
             self.controller.on_completion(&completion)?;
         }
 
-        Ok(chat_record)
+        Ok(SessionStatus::Finished)
     }
 
-    async fn chat_completion(
+    /// Prompt from the controller, `None` ends the session also when input was interrupted
+    fn next_prompt(&mut self) -> anyhow::Result<Option<String>> {
+        match self.controller.create_prompt() {
+            Err(err) if is_interrupted(&err) => Ok(None),
+            prompt => prompt,
+        }
+    }
+
+    /// Completion racing against Ctrl-C, which gives `None` instead of killing the process
+    async fn interruptible_completion(
+        &mut self,
+        assistant_name: &str,
+        token_limit: u16,
+        model: &str,
+        messages: Vec<ChatCompletionRequestMessage>,
+    ) -> anyhow::Result<Option<ChatMessage>> {
+        interruptible(self.chat_completion(assistant_name, token_limit, model, messages))
+            .await
+            .transpose()
+    }
+
+    /// Failed tracking is reported, the answer was already paid for
+    fn track_usage(
         &self,
-        token_limit: &u16,
-        assistant: &ChatAssistant,
+        assistant_name: &str,
+        model: &str,
+        usage: &TokenUsage,
+    ) -> anyhow::Result<()> {
+        let Some(usage_tracker) = &self.usage_tracker else {
+            return Ok(());
+        };
+        if let Err(err) = usage_tracker.track(assistant_name, model, usage) {
+            self.controller
+                .on_command_output(&format!("failed to track usage: {err:#}"))?;
+        }
+        Ok(())
+    }
+
+    fn record(&mut self, record: &ChatRecord) -> anyhow::Result<()> {
+        match &mut self.recorder {
+            Some(recorder) => recorder
+                .record(record)
+                .context("failed to save chat record"),
+            None => Ok(()),
+        }
+    }
+
+    /// Failed save in the middle of session is reported, it shouldn't end the session
+    fn record_turn(&mut self, record: &ChatRecord) -> anyhow::Result<()> {
+        if let Err(err) = self.record(record) {
+            self.controller.on_command_output(&format!("{err:#}"))?;
+        }
+        Ok(())
+    }
+
+    fn finish_record(
+        &mut self,
+        record: ChatRecord,
+        result: anyhow::Result<SessionStatus>,
+    ) -> anyhow::Result<ChatRecord> {
+        let status = match &result {
+            Ok(status) => *status,
+            Err(_) => SessionStatus::Errored,
+        };
+        let record = record.with_status(status);
+        let saved = self.record(&record);
+
+        result?;
+        saved?;
+        Ok(record)
+    }
+
+    /// Refuses completion over hard budget, warns over soft one
+    fn check_budgets(&self) -> anyhow::Result<()> {
+        let Some(usage_tracker) = &self.usage_tracker else {
+            return Ok(());
+        };
+        if let Some(warning) = usage_tracker.check_budgets()? {
+            self.controller.on_command_output(&warning)?;
+        }
+        Ok(())
+    }
+
+    async fn chat_completion(
+        &mut self,
+        assistant_name: &str,
+        token_limit: u16,
+        model: &str,
         messages: Vec<ChatCompletionRequestMessage>,
-    ) -> anyhow::Result<String> {
+    ) -> anyhow::Result<ChatMessage> {
+        self.check_budgets()?;
         let request = CreateChatCompletionRequestArgs::default()
-            .model(assistant.model())
+            .model(model)
             .messages(messages)
-            .max_tokens(*token_limit)
+            .max_tokens(token_limit)
             .build()?;
 
+        let start = Instant::now();
         let response = self.client.chat().create(request).await?;
+        let latency = start.elapsed();
+
+        let usage = response.usage.as_ref().map(|usage| TokenUsage {
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+        });
+        if let Some(usage) = &usage {
+            self.usage.add(usage.prompt_tokens, usage.completion_tokens);
+            self.track_usage(assistant_name, &response.model, usage)?;
+        }
         let completion = response
             .choices
             .first()
@@ -101,70 +408,194 @@ where
             .message
             .content
             .as_ref()
-            .context("message content is empty")?
-            .to_string();
+            .context("message content is empty")?;
 
-        Ok(completion)
+        let generation = Generation {
+            model: Some(response.model.to_owned()),
+            max_tokens: Some(token_limit),
+            usage,
+            latency_ms: latency.as_millis() as u64,
+        };
+        Ok(ChatMessage::new_assistant(completion).with_generation(generation))
     }
 }
 
+struct ChatSession {
+    creation_date: DateTime<Local>,
+    assistant_name: String,
+    model: String,
+    token_limit: Option<u16>,
+    messages: ChatMessagesBuilder,
+}
+
+impl ChatSession {
+    fn new(assistant: &ChatAssistant) -> Self {
+        Self {
+            creation_date: Local::now(),
+            assistant_name: assistant.name().to_owned(),
+            model: assistant.model().to_owned(),
+            token_limit: assistant.token_limit(),
+            messages: ChatMessagesBuilder::new(assistant.system()),
+        }
+    }
+
+    fn resume(&mut self, record: ChatRecord) {
+        if let Some(system_prompt) = record.system_prompt() {
+            self.messages.set_system(system_prompt);
+        }
+        self.messages.messages = record.messages().to_vec();
+        self.messages.system_changed = record.system_prompt_changed();
+    }
+
+    fn switch_assistant(&mut self, assistant: &ChatAssistant) {
+        self.assistant_name = assistant.name().to_owned();
+        self.model = assistant.model().to_owned();
+        self.token_limit = assistant.token_limit();
+        self.messages.set_system(assistant.system());
+    }
+
+    fn to_chat_record(&self, status: SessionStatus) -> ChatRecord {
+        ChatRecord::new(&self.assistant_name)
+            .with_messages(self.messages.messages.to_vec())
+            .with_creation_date(self.creation_date)
+            .with_model(&self.model)
+            .with_system_prompt(self.messages.system())
+            .with_system_prompt_changed(self.messages.system_changed)
+            .with_status(status)
+    }
+}
+
+/// Ctrl-C pressed at a prompt comes as an interrupted read, terminal is in raw mode then
+fn is_interrupted(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        cause
+            .downcast_ref::<io::Error>()
+            .is_some_and(|err| err.kind() == io::ErrorKind::Interrupted)
+    })
+}
+
 struct ChatMessagesBuilder {
-    messages: Vec<ChatCompletionRequestMessage>,
+    system: String,
+    messages: Vec<ChatMessage>,
+    /// System message was replaced after the first message, until the messages are cleared
+    system_changed: bool,
 }
 
 impl ChatMessagesBuilder {
-    fn new(system_message: &str) -> anyhow::Result<Self> {
-        Ok(Self {
-            messages: vec![ChatCompletionRequestSystemMessageArgs::default()
-                .content(system_message)
-                .build()?
-                .into()],
-        })
+    fn new(system_message: &str) -> Self {
+        Self {
+            system: system_message.to_owned(),
+            messages: vec![],
+            system_changed: false,
+        }
+    }
+
+    fn add(&mut self, message: ChatMessage) -> &mut Self {
+        self.messages.push(message);
+        self
     }
 
-    fn add_user(&mut self, prompt: &str) -> anyhow::Result<&mut Self> {
-        self.messages.push(
-            ChatCompletionRequestUserMessageArgs::default()
-                .content(prompt)
-                .build()?
-                .into(),
-        );
-        Ok(self)
+    fn system(&self) -> &str {
+        &self.system
     }
 
-    fn add_assistant(&mut self, completion: &str) -> anyhow::Result<&mut Self> {
-        self.messages.push(
-            ChatCompletionRequestAssistantMessageArgs::default()
-                .content(completion)
-                .build()?
-                .into(),
-        );
-        Ok(self)
+    fn set_system(&mut self, system_message: &str) -> &mut Self {
+        if !self.messages.is_empty() && self.system != system_message {
+            self.system_changed = true;
+        }
+        self.system = system_message.to_owned();
+        self
     }
 
-    fn build(&self) -> Vec<ChatCompletionRequestMessage> {
-        self.messages.to_vec()
+    fn clear(&mut self) {
+        self.messages.clear();
+        self.system_changed = false;
     }
 
-    fn to_chat_record(self, assistant_name: &str) -> ChatRecord {
-        let messages = self
+    /// Removes messages back to and including the last user prompt
+    fn undo(&mut self) -> bool {
+        let Some(index) = self
             .messages
-            .into_iter()
-            .filter_map(|message| match message {
-                ChatCompletionRequestMessage::User(message) => {
-                    let ChatCompletionRequestUserMessageContent::Text(text) = message.content
-                    else {
-                        return None;
-                    };
-                    Some(ChatMessage::new_user(&text))
-                }
-                ChatCompletionRequestMessage::Assistant(message) => {
-                    Some(ChatMessage::new_assistant(&message.content?))
-                }
-                _ => None,
-            })
-            .collect();
+            .iter()
+            .rposition(|message| message.role() == Role::User)
+        else {
+            return false;
+        };
+
+        self.messages.truncate(index);
+        true
+    }
+
+    fn ends_with_assistant(&self) -> bool {
+        self.messages
+            .last()
+            .is_some_and(|message| message.role() == Role::Assistant)
+    }
+
+    fn replace_last_assistant(&mut self, message: ChatMessage) {
+        if self.ends_with_assistant() {
+            self.messages.pop();
+        }
+        self.messages.push(message);
+    }
+
+    fn build(&self) -> anyhow::Result<Vec<ChatCompletionRequestMessage>> {
+        let mut messages = vec![ChatCompletionRequestSystemMessageArgs::default()
+            .content(&self.system)
+            .build()?
+            .into()];
+
+        for message in &self.messages {
+            let message = match message.role() {
+                Role::User => ChatCompletionRequestUserMessageArgs::default()
+                    .content(message.content())
+                    .build()?
+                    .into(),
+                Role::Assistant => ChatCompletionRequestAssistantMessageArgs::default()
+                    .content(message.content())
+                    .build()?
+                    .into(),
+            };
+            messages.push(message);
+        }
+
+        Ok(messages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_system_changed_after_first_message() {
+        let mut messages = ChatMessagesBuilder::new("Be brief");
+        messages.set_system("Be verbose");
+        assert!(!messages.system_changed);
+
+        messages.add(ChatMessage::new_user("hi"));
+        messages.set_system("Be verbose");
+        assert!(!messages.system_changed);
+        messages.set_system("Be brief");
+        assert!(messages.system_changed);
+
+        messages.clear();
+        assert!(!messages.system_changed);
+    }
+
+    #[test]
+    fn test_replace_last_assistant() -> anyhow::Result<()> {
+        let mut messages = ChatMessagesBuilder::new("system");
+        messages.add(ChatMessage::new_user("question"));
+        assert!(!messages.ends_with_assistant());
+
+        messages.add(ChatMessage::new_assistant("first answer"));
+        assert!(messages.ends_with_assistant());
+        assert_eq!(messages.build()?.len(), 3);
 
-        ChatRecord::new(assistant_name).with_messages(messages)
+        messages.replace_last_assistant(ChatMessage::new_assistant("second answer"));
+        let contents: Vec<_> = messages.messages.iter().map(|m| m.content()).collect();
+        assert_eq!(contents, ["question", "second answer"]);
+        Ok(())
     }
 }
@@ -0,0 +1,54 @@
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Once;
+use tokio::sync::Notify;
+
+/// Exit status of a process killed by SIGINT
+const INTERRUPTED_EXIT_CODE: i32 = 128 + 2;
+
+static WATCH: Once = Once::new();
+static WAITING: AtomicBool = AtomicBool::new(false);
+static INTERRUPTED: Notify = Notify::const_new();
+
+/// Awaits the future unless Ctrl-C is pressed first, which gives `None`.
+///
+/// Tokio keeps its SIGINT handler once installed, so a single watcher handles Ctrl-C for
+/// the rest of the process and exits at any time no future is awaited, like without the handler
+pub(crate) async fn interruptible<F: Future>(future: F) -> Option<F::Output> {
+    WATCH.call_once(|| {
+        tokio::spawn(watch_ctrl_c());
+    });
+
+    let interrupted = INTERRUPTED.notified();
+    tokio::pin!(interrupted);
+    interrupted.as_mut().enable();
+
+    WAITING.store(true, Ordering::SeqCst);
+    let output = tokio::select! {
+        output = future => Some(output),
+        _ = interrupted => None,
+    };
+    WAITING.store(false, Ordering::SeqCst);
+
+    output
+}
+
+async fn watch_ctrl_c() {
+    while tokio::signal::ctrl_c().await.is_ok() {
+        match WAITING.load(Ordering::SeqCst) {
+            true => INTERRUPTED.notify_waiters(),
+            false => std::process::exit(INTERRUPTED_EXIT_CODE),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_interruptible_completes() {
+        assert_eq!(interruptible(async { 42 }).await, Some(42));
+        assert!(!WAITING.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,271 @@
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+
+const HUNK_HEADER_PATTERN: &str =
+    r"^@@ *(?:-(?P<old_start>\d+)(?:,(?P<old_count>\d+))? +\+\d+(?:,(?P<new_count>\d+))?)? *@@";
+const NULL_PATH: &str = "/dev/null";
+
+#[derive(Debug, PartialEq)]
+enum HunkLine {
+    Context(String),
+    Remove(String),
+    Add(String),
+}
+
+#[derive(Debug)]
+struct Hunk {
+    old_start: Option<usize>,
+    lines: Vec<HunkLine>,
+}
+
+#[derive(Debug)]
+pub struct FilePatch {
+    old_path: Option<String>,
+    new_path: Option<String>,
+    hunks: Vec<Hunk>,
+}
+
+impl Hunk {
+    fn old_lines(&self) -> Vec<&str> {
+        self.lines
+            .iter()
+            .filter_map(|line| match line {
+                HunkLine::Context(line) | HunkLine::Remove(line) => Some(line.as_str()),
+                HunkLine::Add(_) => None,
+            })
+            .collect()
+    }
+
+    fn new_lines(&self) -> Vec<&str> {
+        self.lines
+            .iter()
+            .filter_map(|line| match line {
+                HunkLine::Context(line) | HunkLine::Add(line) => Some(line.as_str()),
+                HunkLine::Remove(_) => None,
+            })
+            .collect()
+    }
+}
+
+impl FilePatch {
+    /// Path of the patched file, the old one when file is deleted
+    pub fn path(&self) -> &str {
+        self.new_path
+            .as_deref()
+            .or(self.old_path.as_deref())
+            .unwrap_or_default()
+    }
+
+    pub fn is_new_file(&self) -> bool {
+        self.old_path.is_none()
+    }
+
+    pub fn is_deleted_file(&self) -> bool {
+        self.new_path.is_none()
+    }
+
+    /// Applies hunks to the content, hunks are located by their context so line numbers may be off
+    pub fn apply(&self, content: &str) -> Result<String> {
+        let mut lines: Vec<&str> = content.lines().collect();
+        let mut search_from = 0;
+
+        for (index, hunk) in self.hunks.iter().enumerate() {
+            let old_lines = hunk.old_lines();
+            let expected = hunk
+                .old_start
+                .map(|s| s.saturating_sub(1))
+                .unwrap_or(search_from);
+            let position =
+                find_lines(&lines, &old_lines, search_from, expected).with_context(|| {
+                    format!(
+                        "hunk {} does not match content of {}",
+                        index + 1,
+                        self.path()
+                    )
+                })?;
+
+            let new_lines = hunk.new_lines();
+            lines.splice(
+                position..position + old_lines.len(),
+                new_lines.iter().copied(),
+            );
+            search_from = position + new_lines.len();
+        }
+
+        let mut patched = lines.join("\n");
+        if !patched.is_empty() && (content.ends_with('\n') || content.is_empty()) {
+            patched.push('\n');
+        }
+
+        Ok(patched)
+    }
+}
+
+/// Finds position of needle at or after `from`, preferring the one closest to `expected`
+fn find_lines(lines: &[&str], needle: &[&str], from: usize, expected: usize) -> Option<usize> {
+    if needle.is_empty() {
+        return Some(expected.clamp(from, lines.len()));
+    }
+
+    (from..=lines.len().saturating_sub(needle.len()))
+        .filter(|start| {
+            lines.len() - start >= needle.len()
+                && lines[*start..]
+                    .iter()
+                    .zip(needle)
+                    .all(|(line, needle_line)| line.trim_end() == needle_line.trim_end())
+        })
+        .min_by_key(|start| start.abs_diff(expected))
+}
+
+/// Parses files and hunks of the diff, `--- ` and `+++ ` lines inside a hunk are its removed
+/// and added lines, like `-- comment` removed from SQL
+pub fn parse_unified_diff(diff: &str) -> Result<Vec<FilePatch>> {
+    let hunk_header = Regex::new(HUNK_HEADER_PATTERN)?;
+    let lines: Vec<&str> = diff.lines().collect();
+    let mut patches: Vec<FilePatch> = vec![];
+    let mut old_path = None;
+    let mut after_old_path = false;
+    // old and new lines left in the current hunk, unknown for headers without line counts
+    let mut remaining: Option<(usize, usize)> = None;
+
+    for (index, line) in lines.iter().enumerate() {
+        let in_hunk = remaining.is_some_and(|(old, new)| old > 0 || new > 0);
+        if !in_hunk {
+            // file header is a `--- ` line followed by `+++ ` one
+            let next_is_new_path = lines
+                .get(index + 1)
+                .is_some_and(|next| next.starts_with("+++ "));
+            if let Some(path) = line.strip_prefix("--- ").filter(|_| next_is_new_path) {
+                old_path = parse_path(path);
+                after_old_path = true;
+                continue;
+            }
+            if let Some(path) = line.strip_prefix("+++ ").filter(|_| after_old_path) {
+                patches.push(FilePatch {
+                    old_path: old_path.take(),
+                    new_path: parse_path(path),
+                    hunks: vec![],
+                });
+                after_old_path = false;
+                remaining = None;
+                continue;
+            }
+        }
+        after_old_path = false;
+
+        let Some(patch) = patches.last_mut() else {
+            continue;
+        };
+
+        if let Some(captures) = hunk_header.captures(line).filter(|_| !in_hunk) {
+            let number = |name: &str| {
+                captures
+                    .name(name)
+                    .and_then(|number| number.as_str().parse::<usize>().ok())
+            };
+            let old_start = number("old_start");
+            remaining = old_start.map(|_| {
+                (
+                    number("old_count").unwrap_or(1),
+                    number("new_count").unwrap_or(1),
+                )
+            });
+            patch.hunks.push(Hunk {
+                old_start,
+                lines: vec![],
+            });
+            continue;
+        }
+
+        let Some(hunk) = patch.hunks.last_mut() else {
+            continue;
+        };
+        let hunk_line = match line.chars().next() {
+            Some('+') => HunkLine::Add(line[1..].to_owned()),
+            Some('-') => HunkLine::Remove(line[1..].to_owned()),
+            Some(' ') => HunkLine::Context(line[1..].to_owned()),
+            None => HunkLine::Context(String::new()),
+            Some(_) => continue,
+        };
+        if let Some((old, new)) = &mut remaining {
+            if !matches!(hunk_line, HunkLine::Add(_)) {
+                *old = old.saturating_sub(1);
+            }
+            if !matches!(hunk_line, HunkLine::Remove(_)) {
+                *new = new.saturating_sub(1);
+            }
+        }
+        hunk.lines.push(hunk_line);
+    }
+
+    if patches.is_empty() || patches.iter().any(|patch| patch.hunks.is_empty()) {
+        bail!("no file changes found in the diff");
+    }
+
+    Ok(patches)
+}
+
+fn parse_path(path: &str) -> Option<String> {
+    let path = path.split('\t').next().unwrap_or_default().trim();
+    if path == NULL_PATH {
+        return None;
+    }
+
+    let path = path
+        .strip_prefix("a/")
+        .or_else(|| path.strip_prefix("b/"))
+        .unwrap_or(path);
+
+    Some(path.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_unified_diff() -> Result<()> {
+        let content = "fn main() {\n    let a = 1;\n    println!(\"{a}\");\n}\n";
+        let diff = "--- a/src/main.rs\n+++ b/src/main.rs\n@@ -10,3 +10,3 @@\n fn main() {\n-    let a = 1;\n+    let a = 2;\n     println!(\"{a}\");\n";
+
+        let patches = parse_unified_diff(diff)?;
+        assert_eq!(patches.len(), 1);
+        assert_eq!(patches[0].path(), "src/main.rs");
+        assert_eq!(
+            patches[0].apply(content)?,
+            "fn main() {\n    let a = 2;\n    println!(\"{a}\");\n}\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff_lines_looking_like_file_headers() -> Result<()> {
+        let content = "-- old comment\nselect 1;\n";
+        let diff = "--- a/query.sql\n+++ b/query.sql\n@@ -1,2 +1,2 @@\n--- old comment\n+++ new comment\n select 1;\n--- a/other.sql\n+++ b/other.sql\n@@ -1 +1 @@\n-a\n+b\n";
+
+        let patches = parse_unified_diff(diff)?;
+        assert_eq!(patches.len(), 2);
+        assert_eq!(patches[0].path(), "query.sql");
+        assert_eq!(patches[0].apply(content)?, "++ new comment\nselect 1;\n");
+        assert_eq!(patches[1].path(), "other.sql");
+
+        let diff = "--- a/query.sql\n+++ b/query.sql\n@@ @@\n--- old comment\n+-- new comment\n";
+        assert_eq!(parse_unified_diff(diff)?[0].hunks[0].lines.len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_mismatched_diff() -> Result<()> {
+        let diff = "--- /dev/null\n+++ b/new.txt\n@@ @@\n-missing\n+line\n";
+        let patches = parse_unified_diff(diff)?;
+
+        assert!(patches[0].is_new_file());
+        assert!(patches[0].apply("other\n").is_err());
+        assert!(parse_unified_diff("just text").is_err());
+
+        Ok(())
+    }
+}
@@ -1,8 +1,12 @@
-use anyhow::Result;
+mod extractor;
+
+use anyhow::{bail, Result};
+use extractor::Extractors;
 use itertools::Itertools;
 use regex::Regex;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
+use std::path::Path;
 use std::{fs, io, path};
 
 const PLACEHOLDER_KEY_PATTERN: &str = r"\{([^{]*?)}";
@@ -37,8 +41,7 @@ impl FilePlaceholder {
             return Ok(self.file_path.to_owned());
         }
 
-        let content = fs::read_to_string(path)?;
-        Ok(content)
+        read_content(&path)
     }
 }
 
@@ -56,17 +59,30 @@ impl FileSlicePlaceholder {
             return Ok(self.file_path.to_owned());
         }
 
+        if let Some(extractor) = Extractors::default().get(&path) {
+            let pages = extractor.extract(&path)?;
+            let content_sliced = match extractor.is_paged() {
+                true => self.slice(pages.into_iter()).join("\n\n"),
+                false => self.slice(pages.join("\n").lines()).join("\n"),
+            };
+            return Ok(content_sliced);
+        }
+
         let file = File::open(path)?;
         let reader = BufReader::new(file);
-        let content_sliced = reader
-            .lines()
-            .skip(self.from_line - 1)
-            .take(self.to_line - self.from_line + 1)
+        let content_sliced = self
+            .slice(reader.lines())
             .collect::<io::Result<Vec<String>>>()?
             .join("\n");
 
         Ok(content_sliced)
     }
+
+    fn slice<I: Iterator>(&self, items: I) -> impl Iterator<Item = I::Item> {
+        items
+            .skip(self.from_line - 1)
+            .take(self.to_line - self.from_line + 1)
+    }
 }
 
 impl From<String> for FileSlicePlaceholder {
@@ -117,6 +133,14 @@ impl Placeholder {
         }
     }
 
+    pub fn file_path(&self) -> Option<&str> {
+        match self {
+            Placeholder::File(file) => Some(&file.file_path),
+            Placeholder::FileSlice(file_slice) => Some(&file_slice.file_path),
+            Placeholder::Unknown(_) => None,
+        }
+    }
+
     pub fn is_unknown(&self) -> bool {
         matches!(*self, Placeholder::Unknown(_))
     }
@@ -148,6 +172,51 @@ pub fn replace_placeholders(mut text: String) -> Result<String> {
     Ok(text)
 }
 
+/// Returns paths of files referenced by file and file slice placeholders
+pub fn placeholder_file_paths(text: &str) -> Vec<String> {
+    get_placeholder_keys(text)
+        .into_iter()
+        .filter_map(|key| {
+            Placeholder::from(key)
+                .file_path()
+                .map(|path| path.to_owned())
+        })
+        .unique()
+        .collect()
+}
+
+/// Wraps file placeholders in fenced blocks labeled with file path, so model knows where content comes from
+pub fn label_file_placeholders(text: &str) -> String {
+    get_placeholder_keys(text)
+        .into_iter()
+        .map(Placeholder::from)
+        .fold(text.to_owned(), |text, placeholder| {
+            let Some(path) = placeholder.file_path() else {
+                return text;
+            };
+            let key = format!("{{{}}}", placeholder.key());
+            text.replace(&key, &format!("\n{path}\n```\n{key}\n```\n"))
+        })
+}
+
+/// Reads file the same way as file placeholders do, documents go through their extractors
+pub fn read_file(file_path: &str) -> Result<String> {
+    let path = path::absolute(file_path)?;
+    if !path.is_file() {
+        bail!("file {path:?} does not exist");
+    }
+
+    read_content(&path)
+}
+
+fn read_content(path: &Path) -> Result<String> {
+    let content = match Extractors::default().get(path) {
+        None => fs::read_to_string(path)?,
+        Some(extractor) => extractor.extract(path)?.join("\n\n"),
+    };
+    Ok(content)
+}
+
 fn get_placeholder_keys(text: &str) -> Vec<String> {
     let regex = Regex::new(PLACEHOLDER_KEY_PATTERN).unwrap();
     regex
@@ -164,17 +233,17 @@ fn replace_placeholder(text: &str, placeholder: Placeholder) -> Result<String> {
     }
 
     let regex = Regex::new(&regex::escape(&format!("{{{}}}", placeholder.key())))?;
-    let text = regex.replace_all(&text, placeholder.value()?).to_string();
+    let text = regex.replace_all(text, placeholder.value()?).to_string();
 
     Ok(text)
 }
 
 fn is_file_path(key: &str) -> bool {
-    Regex::new(FILE_PATTERN).unwrap().is_match(&key)
+    Regex::new(FILE_PATTERN).unwrap().is_match(key)
 }
 
 fn is_file_slice(key: &str) -> bool {
-    Regex::new(FILE_SLICE_PATTERN).unwrap().is_match(&key)
+    Regex::new(FILE_SLICE_PATTERN).unwrap().is_match(key)
 }
 
 #[cfg(test)]
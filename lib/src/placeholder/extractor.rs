@@ -0,0 +1,55 @@
+mod docx;
+mod html;
+mod pdf;
+
+use anyhow::Result;
+use docx::DocxExtractor;
+use html::HtmlExtractor;
+use pdf::PdfExtractor;
+use std::collections::HashMap;
+use std::path::Path;
+
+pub(crate) trait Extractor {
+    /// Returns document text split into pages, formats without pages return a single entry
+    fn extract(&self, path: &Path) -> Result<Vec<String>>;
+
+    fn is_paged(&self) -> bool {
+        false
+    }
+}
+
+pub(crate) struct Extractors {
+    extractors: HashMap<String, Box<dyn Extractor>>,
+}
+
+impl Default for Extractors {
+    fn default() -> Self {
+        Self::empty()
+            .with("pdf", PdfExtractor)
+            .with("docx", DocxExtractor)
+            .with("html", HtmlExtractor)
+            .with("htm", HtmlExtractor)
+    }
+}
+
+impl Extractors {
+    pub(crate) fn empty() -> Self {
+        Self {
+            extractors: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn with<E>(mut self, extension: &str, extractor: E) -> Self
+    where
+        E: Extractor + 'static,
+    {
+        self.extractors
+            .insert(extension.to_lowercase(), Box::new(extractor));
+        self
+    }
+
+    pub(crate) fn get(&self, path: &Path) -> Option<&dyn Extractor> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        self.extractors.get(&extension).map(|e| e.as_ref())
+    }
+}
@@ -0,0 +1,100 @@
+use crate::placeholder::extractor::Extractor;
+use anyhow::{Context, Result};
+use quick_xml::escape::resolve_predefined_entity;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use zip::ZipArchive;
+
+const DOCUMENT_ENTRY: &str = "word/document.xml";
+
+pub(crate) struct DocxExtractor;
+
+impl Extractor for DocxExtractor {
+    fn extract(&self, path: &Path) -> Result<Vec<String>> {
+        let mut archive = ZipArchive::new(File::open(path)?)
+            .with_context(|| format!("file {path:?} is not a valid docx archive"))?;
+
+        let mut document = String::new();
+        archive
+            .by_name(DOCUMENT_ENTRY)
+            .with_context(|| format!("docx {path:?} has no {DOCUMENT_ENTRY}"))?
+            .read_to_string(&mut document)?;
+
+        Ok(vec![document_to_markdown(&document)?])
+    }
+}
+
+fn document_to_markdown(document: &str) -> Result<String> {
+    let mut reader = Reader::from_str(document);
+    let mut paragraphs = vec![];
+    let mut paragraph = String::new();
+    let mut in_text = false;
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(tag) if tag.local_name().as_ref() == "t" => in_text = true,
+            Event::End(tag) if tag.local_name().as_ref() == "t" => in_text = false,
+            Event::Empty(tag) if tag.local_name().as_ref() == "pStyle" => {
+                paragraph.insert_str(0, heading_prefix(&tag));
+            }
+            Event::Empty(tag) if tag.local_name().as_ref() == "tab" => paragraph.push('\t'),
+            Event::Empty(tag) if tag.local_name().as_ref() == "br" => paragraph.push('\n'),
+            Event::Text(text) if in_text => paragraph.push_str(&text.xml10_content()),
+            Event::GeneralRef(reference) if in_text => {
+                if let Some(char) = reference.resolve_char_ref()? {
+                    paragraph.push(char);
+                } else if let Some(entity) = resolve_predefined_entity(&reference) {
+                    paragraph.push_str(entity);
+                }
+            }
+            Event::End(tag) if tag.local_name().as_ref() == "p" => {
+                paragraphs.push(std::mem::take(&mut paragraph));
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(paragraphs.join("\n"))
+}
+
+fn heading_prefix(paragraph_style: &BytesStart) -> &'static str {
+    let style = paragraph_style
+        .attributes()
+        .filter_map(|attribute| attribute.ok())
+        .find(|attribute| attribute.key.local_name().as_ref() == "val")
+        .map(|attribute| attribute.value.to_lowercase());
+
+    match style.as_deref() {
+        Some("title") | Some("heading1") => "# ",
+        Some("heading2") => "## ",
+        Some("heading3") => "### ",
+        Some(style) if style.starts_with("heading") => "#### ",
+        Some(style) if style.starts_with("listparagraph") => "- ",
+        _ => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_document_to_markdown() -> Result<()> {
+        let document = r#"<w:document xmlns:w="w"><w:body>
+            <w:p><w:pPr><w:pStyle w:val="Heading1"/></w:pPr><w:r><w:t>Spec</w:t></w:r></w:p>
+            <w:p><w:r><w:t xml:space="preserve">Tom &amp; </w:t></w:r><w:r><w:t>Jerry</w:t></w:r></w:p>
+            <w:p><w:pPr><w:pStyle w:val="ListParagraph"/></w:pPr><w:r><w:t>item</w:t></w:r></w:p>
+        </w:body></w:document>"#;
+
+        assert_eq!(
+            document_to_markdown(document)?,
+            "# Spec\nTom & Jerry\n- item"
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,18 @@
+use crate::placeholder::extractor::Extractor;
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::path::Path;
+
+const TEXT_WIDTH: usize = 100;
+
+pub(crate) struct HtmlExtractor;
+
+impl Extractor for HtmlExtractor {
+    fn extract(&self, path: &Path) -> Result<Vec<String>> {
+        let file = File::open(path)?;
+        let markdown = html2text::from_read(file, TEXT_WIDTH)
+            .with_context(|| format!("failed to convert html {path:?}"))?;
+
+        Ok(vec![markdown.trim_end().to_owned()])
+    }
+}
@@ -0,0 +1,21 @@
+use crate::placeholder::extractor::Extractor;
+use anyhow::{Context, Result};
+use std::path::Path;
+
+pub(crate) struct PdfExtractor;
+
+impl Extractor for PdfExtractor {
+    fn extract(&self, path: &Path) -> Result<Vec<String>> {
+        let pages = pdf_extract::extract_text_by_pages(path)
+            .with_context(|| format!("failed to extract text from pdf {path:?}"))?
+            .into_iter()
+            .map(|page| page.trim().to_owned())
+            .collect();
+
+        Ok(pages)
+    }
+
+    fn is_paged(&self) -> bool {
+        true
+    }
+}
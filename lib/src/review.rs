@@ -0,0 +1,168 @@
+use crate::parse_code_blocks;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let severity = match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{severity}")
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReviewFinding {
+    pub file: String,
+    /// Line in the new version of the file, missing for findings about the whole file
+    #[serde(default)]
+    pub line: Option<u32>,
+    pub severity: Severity,
+    pub suggestion: String,
+}
+
+/// Findings from the review answer with the entries that could not be read as findings
+#[derive(Debug, Default)]
+pub struct ParsedReview {
+    pub findings: Vec<ReviewFinding>,
+    /// JSON of entries missing file or suggestion
+    pub skipped: Vec<String>,
+}
+
+/// Parses findings from JSON answer, which models sometimes wrap in a code block or an object
+pub fn parse_review_findings(completion: &str) -> Result<ParsedReview> {
+    let json = parse_code_blocks(completion)
+        .into_iter()
+        .next()
+        .map(|block| block.code)
+        .unwrap_or_else(|| completion.to_owned());
+
+    let response: Value = serde_json::from_str(json.trim())
+        .with_context(|| format!("review is not valid JSON:\n{completion}"))?;
+    let entries = match response {
+        Value::Array(entries) => entries,
+        Value::Object(mut object) => match object.remove("findings") {
+            Some(Value::Array(entries)) => entries,
+            _ => bail!("review has no findings:\n{completion}"),
+        },
+        _ => bail!("review has no findings:\n{completion}"),
+    };
+
+    let mut review = ParsedReview::default();
+    for entry in entries {
+        match parse_finding(&entry) {
+            Some(finding) => review.findings.push(finding),
+            None => review.skipped.push(entry.to_string()),
+        }
+    }
+
+    review
+        .findings
+        .sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+    Ok(review)
+}
+
+/// Reads finding leniently, unknown severity is info and line that is not a number is left out
+fn parse_finding(entry: &Value) -> Option<ReviewFinding> {
+    let text = |key: &str| entry.get(key)?.as_str().map(str::to_owned);
+    let line = match entry.get("line") {
+        Some(Value::Number(line)) => line.as_u64().and_then(|line| u32::try_from(line).ok()),
+        Some(Value::String(line)) => line.trim().parse().ok(),
+        _ => None,
+    };
+    let severity = entry
+        .get("severity")
+        .and_then(Value::as_str)
+        .and_then(|severity| serde_json::from_value(Value::from(severity.to_lowercase())).ok())
+        .unwrap_or(Severity::Info);
+
+    Some(ReviewFinding {
+        file: text("file")?,
+        line,
+        severity,
+        suggestion: text("suggestion")?,
+    })
+}
+
+/// Prefixes lines with their numbers, so the model can point at lines of whole files
+pub fn number_lines(content: &str) -> String {
+    content
+        .lines()
+        .enumerate()
+        .map(|(index, line)| format!("{:>5} {line}", index + 1))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_review_findings() -> Result<()> {
+        let completion = r#"```json
+[
+  {"file": "src/main.rs", "line": 12, "severity": "error", "suggestion": "Handle the error"},
+  {"file": "src/lib.rs", "severity": "info", "suggestion": "Add docs"}
+]
+```"#;
+
+        let findings = parse_review_findings(completion)?.findings;
+
+        assert_eq!(findings.len(), 2);
+        assert_eq!(findings[0].file, "src/lib.rs");
+        assert_eq!(findings[0].line, None);
+        assert_eq!(findings[1].severity, Severity::Error);
+
+        let wrapped = r#"{"findings": []}"#;
+        assert!(parse_review_findings(wrapped)?.findings.is_empty());
+        assert!(parse_review_findings("Looks good to me").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_review_findings_leniently() -> Result<()> {
+        let completion = r#"[
+  {"file": "a.rs", "line": "7", "severity": "critical", "suggestion": "Check bounds"},
+  {"file": "b.rs", "line": "top", "severity": "Warning", "suggestion": "Rename"},
+  {"file": "c.rs", "line": 3},
+  "not a finding"
+]"#;
+
+        let review = parse_review_findings(completion)?;
+
+        assert_eq!(
+            review.findings,
+            vec![
+                ReviewFinding {
+                    file: "a.rs".to_owned(),
+                    line: Some(7),
+                    severity: Severity::Info,
+                    suggestion: "Check bounds".to_owned(),
+                },
+                ReviewFinding {
+                    file: "b.rs".to_owned(),
+                    line: None,
+                    severity: Severity::Warning,
+                    suggestion: "Rename".to_owned(),
+                },
+            ]
+        );
+        assert_eq!(review.skipped.len(), 2);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,135 @@
+use crate::{shell_command, APP_NAME};
+use anyhow::{anyhow, bail, Context, Result};
+use std::env;
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+/// Takes precedence over every configured source of the key
+pub const API_KEY_ENV: &str = "OPENAI_API_KEY";
+const KEYRING_ACCOUNT: &str = "api_key";
+
+/// Key from `OPENAI_API_KEY` when it is set and not empty
+pub fn env_api_key() -> Option<String> {
+    env::var(API_KEY_ENV)
+        .ok()
+        .map(|key| key.trim().to_owned())
+        .filter(|key| !key.is_empty())
+}
+
+/// Output of command printing the key, like `pass show openai`, only its first line is used
+pub fn command_api_key(shell: &str, command: &str) -> Result<String> {
+    let output = shell_command(shell, command)
+        .stderr(Stdio::inherit())
+        .output()
+        .with_context(|| format!("failed to run api_key_cmd \"{command}\""))?;
+    if !output.status.success() {
+        bail!("api_key_cmd \"{command}\" failed with {}", output.status);
+    }
+
+    let output = String::from_utf8(output.stdout).context("api_key_cmd printed invalid UTF-8")?;
+    output
+        .lines()
+        .next()
+        .map(|key| key.trim().to_owned())
+        .filter(|key| !key.is_empty())
+        .with_context(|| format!("api_key_cmd \"{command}\" printed no key"))
+}
+
+/// Secret Service keyring of the desktop session, accessed with `secret-tool` of libsecret
+pub struct Keyring;
+
+impl Keyring {
+    pub fn get() -> Result<Option<String>> {
+        let output = secret_tool(&["lookup", "service", APP_NAME, "account", KEYRING_ACCOUNT])?
+            .stderr(Stdio::piped())
+            .output()
+            .map_err(secret_tool_error)?;
+
+        // lookup of missing secret fails without any message
+        if !output.status.success() && output.stderr.is_empty() {
+            return Ok(None);
+        }
+        if !output.status.success() {
+            bail!(
+                "secret-tool lookup failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        let key = String::from_utf8(output.stdout)?.trim().to_owned();
+        Ok(Some(key).filter(|key| !key.is_empty()))
+    }
+
+    pub fn set(key: &str) -> Result<()> {
+        let label = format!("{APP_NAME} OpenAI API key");
+        let mut child = secret_tool(&[
+            "store",
+            "--label",
+            &label,
+            "service",
+            APP_NAME,
+            "account",
+            KEYRING_ACCOUNT,
+        ])?
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(secret_tool_error)?;
+
+        child
+            .stdin
+            .take()
+            .context("failed to pass API key to secret-tool")?
+            .write_all(key.as_bytes())?;
+
+        if !child.wait()?.success() {
+            bail!("secret-tool failed to store API key");
+        }
+        Ok(())
+    }
+
+    pub fn delete() -> Result<()> {
+        let status = secret_tool(&["clear", "service", APP_NAME, "account", KEYRING_ACCOUNT])?
+            .status()
+            .map_err(secret_tool_error)?;
+        if !status.success() {
+            bail!("secret-tool failed to remove API key");
+        }
+        Ok(())
+    }
+}
+
+fn secret_tool(args: &[&str]) -> Result<Command> {
+    if env::consts::OS != "linux" {
+        bail!("keyring is supported only on Linux, use api_key_cmd to read key from other stores");
+    }
+
+    let mut command = Command::new("secret-tool");
+    command.args(args);
+    Ok(command)
+}
+
+fn secret_tool_error(err: io::Error) -> anyhow::Error {
+    match err.kind() {
+        io::ErrorKind::NotFound => {
+            anyhow!("secret-tool not found, install libsecret-tools to use keyring")
+        }
+        _ => anyhow::Error::from(err).context("failed to run secret-tool"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(unix)]
+    fn test_command_api_key() -> Result<()> {
+        assert_eq!(
+            command_api_key("/bin/sh", "printf 'sk-test\\nlogin: me\\n'")?,
+            "sk-test"
+        );
+        assert!(command_api_key("/bin/sh", "exit 1").is_err());
+        assert!(command_api_key("/bin/sh", "true").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,657 @@
+use crate::parse_code_blocks;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+const OPERATORS: [&str; 9] = ["&&", "||", ">>", ";", "|", "&", ">", "<", "\n"];
+const SEPARATORS: [&str; 5] = ["&&", "||", ";", "&", "\n"];
+const ELEVATION_PROGRAMS: [&str; 3] = ["sudo", "doas", "su"];
+const SHELL_PROGRAMS: [&str; 10] = [
+    "sh", "bash", "zsh", "fish", "dash", "ksh", "python", "python3", "perl", "ruby",
+];
+const DOWNLOAD_PROGRAMS: [&str; 2] = ["curl", "wget"];
+const DISK_PROGRAMS: [&str; 5] = ["fdisk", "parted", "wipefs", "shred", "mkswap"];
+const POWER_PROGRAMS: [&str; 4] = ["shutdown", "reboot", "poweroff", "halt"];
+const FIND_ACTIONS: [&str; 5] = ["-exec", "-execdir", "-ok", "-okdir", "-delete"];
+const CRITICAL_PATHS: [&str; 5] = ["/", "/*", "~", "~/", "*"];
+const FAILURE_STDERR_LINES: usize = 40;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RiskLevel {
+    Low,
+    Medium,
+    High,
+    /// Command matches the configured deny list and must not be executed
+    Denied,
+}
+
+impl Display for RiskLevel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let level = match self {
+            RiskLevel::Low => "low",
+            RiskLevel::Medium => "medium",
+            RiskLevel::High => "high",
+            RiskLevel::Denied => "denied",
+        };
+        write!(f, "{level}")
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct CommandAssessment {
+    pub risk: RiskLevel,
+    pub reasons: Vec<String>,
+}
+
+impl CommandAssessment {
+    fn low() -> Self {
+        Self {
+            risk: RiskLevel::Low,
+            reasons: vec![],
+        }
+    }
+
+    fn flag(&mut self, risk: RiskLevel, reason: String) {
+        self.risk = self.risk.max(risk);
+        self.reasons.push(reason);
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Redirection {
+    operator: String,
+    target: String,
+}
+
+/// Single program invocation, part of a pipeline or command list
+#[derive(Debug, Default, PartialEq)]
+struct SimpleCommand {
+    words: Vec<String>,
+    redirections: Vec<Redirection>,
+    piped_from: Option<String>,
+}
+
+impl SimpleCommand {
+    fn program(&self) -> Option<&str> {
+        self.words.first().map(|word| program_name(word))
+    }
+
+    fn args(&self) -> &[String] {
+        self.words.get(1..).unwrap_or_default()
+    }
+
+    fn has_flag(&self, short: char, long: &str) -> bool {
+        self.args().iter().any(|arg| match arg.strip_prefix("--") {
+            Some(name) => name == long,
+            None => arg.starts_with('-') && arg[1..].contains(short),
+        })
+    }
+
+    /// Command run by wrappers like `sudo`, `env` or `xargs`, without their own options,
+    /// `None` for other programs
+    fn wrapped(&self) -> Option<SimpleCommand> {
+        let program = self.program()?;
+        let options_with_value: &[&str] = match program {
+            "sudo" | "doas" => &["-u", "-g", "-h", "-p", "-U", "-C", "-D", "-r", "-t"],
+            "xargs" => &["-I", "-n", "-P", "-L", "-d", "-E", "-s", "-a"],
+            "env" => &["-u", "-C"],
+            "nice" => &["-n"],
+            "nohup" | "time" | "exec" | "command" => &[],
+            _ => return None,
+        };
+
+        let mut args = self.args().iter().peekable();
+        while let Some(arg) = args.peek() {
+            if arg.starts_with('-') {
+                let takes_value = options_with_value.contains(&arg.as_str());
+                args.next();
+                if takes_value {
+                    args.next();
+                }
+            } else if program == "env" && is_assignment(arg) {
+                args.next();
+            } else {
+                break;
+            }
+        }
+
+        Some(SimpleCommand {
+            words: args.cloned().collect(),
+            redirections: vec![],
+            piped_from: self.piped_from.to_owned(),
+        })
+    }
+
+    /// The command followed by the ones it runs through wrappers, like `docker` in `sudo env docker`
+    fn unwrapped(&self) -> Vec<SimpleCommand> {
+        let mut commands = vec![];
+        let mut wrapped = self.wrapped();
+        while let Some(command) = wrapped.filter(|command| !command.words.is_empty()) {
+            wrapped = command.wrapped();
+            commands.push(command);
+        }
+        commands
+    }
+}
+
+/// Commands allowed or denied by user, entries match commands starting with the same words
+#[derive(Debug, Default)]
+pub struct CommandPolicy {
+    allow: Vec<Vec<String>>,
+    deny: Vec<Vec<String>>,
+}
+
+impl CommandPolicy {
+    pub fn new(allow: &[String], deny: &[String]) -> Self {
+        let split = |entries: &[String]| {
+            entries
+                .iter()
+                .map(|entry| entry.split_whitespace().map(str::to_owned).collect())
+                .filter(|words: &Vec<String>| !words.is_empty())
+                .collect()
+        };
+        Self {
+            allow: split(allow),
+            deny: split(deny),
+        }
+    }
+
+    /// Commands that can't be parsed or run code hidden from the parser are high risk
+    pub fn assess(&self, command: &str) -> CommandAssessment {
+        let mut assessment = CommandAssessment::low();
+        if has_substitution(command) {
+            assessment.flag(
+                RiskLevel::High,
+                "command substitution runs commands that are not assessed".to_owned(),
+            );
+        }
+        let commands = match parse_command(command) {
+            Ok(commands) => commands,
+            Err(err) => {
+                assessment.flag(RiskLevel::High, format!("could not parse command: {err}"));
+                return assessment;
+            }
+        };
+
+        for command in &commands {
+            let denied = std::iter::once(command)
+                .chain(&command.unwrapped())
+                .find_map(|command| self.deny.iter().find(|entry| starts_with(command, entry)))
+                .cloned();
+            if let Some(entry) = denied {
+                assessment.flag(
+                    RiskLevel::Denied,
+                    format!("\"{}\" is on the deny list", entry.join(" ")),
+                );
+                continue;
+            }
+
+            // allowed command can still overwrite files or devices with its output
+            for redirection in &command.redirections {
+                classify_redirection(redirection, &mut assessment);
+            }
+            if !self.allow.iter().any(|entry| starts_with(command, entry)) {
+                classify(command, &mut assessment);
+            }
+        }
+
+        assessment
+    }
+}
+
+/// Extracts command from completion, models tend to wrap it in markdown fences despite instructions
+pub fn strip_code_fences(completion: &str) -> String {
+    let blocks = parse_code_blocks(completion);
+    if blocks.is_empty() {
+        let trimmed = completion.trim();
+        return match trimmed.len() > 1 && trimmed.starts_with('`') && trimmed.ends_with('`') {
+            true => trimmed.trim_matches('`').trim().to_owned(),
+            false => trimmed.to_owned(),
+        };
+    }
+
+    blocks
+        .iter()
+        .map(|block| block.code.trim_end())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Removes code fence only when it wraps the whole completion, fenced snippets inside text are kept
+pub fn strip_enclosing_code_fence(completion: &str) -> String {
+    let trimmed = completion.trim();
+    let line_count = trimmed.lines().count();
+    match parse_code_blocks(trimmed).as_slice() {
+        [block] if line_count >= 2 && block.code.lines().count() == line_count - 2 => {
+            block.code.trim_end().to_owned()
+        }
+        _ => trimmed.to_owned(),
+    }
+}
+
+/// Prompt asking the model to fix a failed command, stderr is cut to its last lines
+pub fn command_failure_prompt(command: &str, exit_code: Option<i32>, stderr: &str) -> String {
+    let status = match exit_code {
+        Some(code) => format!("failed with exit code {code}"),
+        None => "was terminated by a signal".to_owned(),
+    };
+    let lines: Vec<&str> = stderr.trim_end().lines().collect();
+    let stderr_tail = lines[lines.len().saturating_sub(FAILURE_STDERR_LINES)..].join("\n");
+
+    format!("The command:\n```\n{command}\n```\n{status}, stderr:\n```\n{stderr_tail}\n```\nRespond with a fixed command")
+}
+
+fn classify(command: &SimpleCommand, assessment: &mut CommandAssessment) {
+    let Some(program) = command.program() else {
+        return;
+    };
+    let args = command.args();
+
+    if ELEVATION_PROGRAMS.contains(&program) {
+        assessment.flag(
+            RiskLevel::High,
+            format!("{program} runs with elevated privileges"),
+        );
+    }
+    if program == "xargs" {
+        assessment.flag(
+            RiskLevel::High,
+            "xargs runs commands with arguments read from input".to_owned(),
+        );
+    }
+    if let Some(wrapped) = command.wrapped() {
+        if !wrapped.words.is_empty() {
+            classify(&wrapped, assessment);
+        }
+        return;
+    }
+    if ELEVATION_PROGRAMS.contains(&program) {
+        return;
+    }
+
+    if SHELL_PROGRAMS.contains(&program) {
+        if let Some(source) = &command.piped_from {
+            if DOWNLOAD_PROGRAMS.contains(&source.as_str()) {
+                assessment.flag(
+                    RiskLevel::High,
+                    format!("downloaded content is piped into {program}"),
+                );
+            }
+        }
+        if command.has_flag('c', "command")
+            || (matches!(program, "perl" | "ruby") && command.has_flag('e', "e"))
+        {
+            assessment.flag(
+                RiskLevel::High,
+                format!("{program} runs inline code that is not assessed"),
+            );
+        }
+    }
+
+    match program {
+        "eval" => assessment.flag(
+            RiskLevel::High,
+            "eval runs code that is not assessed".to_owned(),
+        ),
+        "find" => {
+            if let Some(action) = args.iter().find(|arg| FIND_ACTIONS.contains(&arg.as_str())) {
+                assessment.flag(
+                    RiskLevel::High,
+                    format!("find {action} acts on every matched file"),
+                );
+            }
+        }
+        "rm" => {
+            let recursive =
+                command.has_flag('r', "recursive") || command.has_flag('R', "recursive");
+            let force = command.has_flag('f', "force");
+            let critical = args
+                .iter()
+                .find(|arg| CRITICAL_PATHS.contains(&arg.as_str()));
+            match (recursive, force, critical) {
+                (true, _, Some(path)) => {
+                    assessment.flag(RiskLevel::High, format!("rm recursively deletes {path}"))
+                }
+                (true, true, None) => assessment.flag(
+                    RiskLevel::High,
+                    "rm -rf deletes files recursively without prompting".to_owned(),
+                ),
+                _ => assessment.flag(RiskLevel::Medium, "rm deletes files".to_owned()),
+            }
+        }
+        "dd" if args.iter().any(|arg| arg.starts_with("of=")) => assessment.flag(
+            RiskLevel::High,
+            "dd writes raw data to output file or device".to_owned(),
+        ),
+        "chmod" | "chown" if command.has_flag('R', "recursive") => assessment.flag(
+            RiskLevel::Medium,
+            format!("{program} changes permissions recursively"),
+        ),
+        "git" => classify_git(args, assessment),
+        _ if program.starts_with("mkfs") => {
+            assessment.flag(RiskLevel::High, format!("{program} formats a filesystem"))
+        }
+        _ if DISK_PROGRAMS.contains(&program) => {
+            assessment.flag(RiskLevel::High, format!("{program} modifies disks"))
+        }
+        _ if POWER_PROGRAMS.contains(&program) => {
+            assessment.flag(RiskLevel::High, format!("{program} stops the system"))
+        }
+        _ => {}
+    }
+}
+
+fn classify_git(args: &[String], assessment: &mut CommandAssessment) {
+    let has = |value: &str| args.iter().any(|arg| arg == value);
+    let reason = match args.first().map(String::as_str) {
+        Some("push") if has("--force") || has("-f") => "git push --force rewrites remote history",
+        Some("reset") if has("--hard") => "git reset --hard discards local changes",
+        Some("clean")
+            if args
+                .iter()
+                .any(|arg| arg.starts_with('-') && arg.contains('f')) =>
+        {
+            "git clean removes untracked files"
+        }
+        _ => return,
+    };
+    assessment.flag(RiskLevel::Medium, reason.to_owned());
+}
+
+fn classify_redirection(redirection: &Redirection, assessment: &mut CommandAssessment) {
+    let target = &redirection.target;
+    if redirection.operator == "<" || target.starts_with('&') || target == "/dev/null" {
+        return;
+    }
+
+    if target.starts_with("/dev/") {
+        assessment.flag(
+            RiskLevel::High,
+            format!("output is written to device {target}"),
+        );
+    } else if redirection.operator == ">" && Path::new(target).exists() {
+        assessment.flag(
+            RiskLevel::Medium,
+            format!("redirection overwrites existing file {target}"),
+        );
+    }
+}
+
+/// Command substitution in backticks or `$(…)` and process substitution outside of single quotes
+fn has_substitution(command: &str) -> bool {
+    let mut chars = command.chars().peekable();
+    let mut quote = None;
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some('\''), '\'') | (Some('"'), '"') => quote = None,
+            (Some('\''), _) => {}
+            (_, '\\') => {
+                chars.next();
+            }
+            (_, '`') => return true,
+            (_, '$') if chars.peek() == Some(&'(') => return true,
+            (None, '<' | '>') if chars.peek() == Some(&'(') => return true,
+            (None, '\'' | '"') => quote = Some(c),
+            _ => {}
+        }
+    }
+
+    false
+}
+
+fn starts_with(command: &SimpleCommand, entry: &[String]) -> bool {
+    let mut words = command.words.iter();
+    let Some(program) = words.next() else {
+        return false;
+    };
+    let Some((entry_program, entry_args)) = entry.split_first() else {
+        return false;
+    };
+
+    program_name(program) == entry_program
+        && entry_args.len() <= command.args().len()
+        && entry_args.iter().zip(words).all(|(a, b)| a == b)
+}
+
+fn program_name(word: &str) -> &str {
+    word.rsplit('/').next().unwrap_or(word)
+}
+
+fn parse_command(command: &str) -> Result<Vec<SimpleCommand>, String> {
+    let tokens = tokenize(command)?;
+    let mut commands = vec![];
+    let mut current = SimpleCommand::default();
+    let mut tokens = tokens.into_iter().peekable();
+
+    while let Some(token) = tokens.next() {
+        match token {
+            Token::Word(word) if current.words.is_empty() && is_assignment(&word) => {}
+            Token::Word(word) => current.words.push(word),
+            Token::Operator(operator) if operator == ">" || operator == ">>" || operator == "<" => {
+                // file descriptor prefix like `2>` is tokenized as a separate word
+                if current
+                    .words
+                    .last()
+                    .is_some_and(|word| word == "2" || word == "1")
+                {
+                    current.words.pop();
+                }
+                let target = match tokens.next() {
+                    Some(Token::Word(target)) => target,
+                    Some(Token::Operator(fd)) if fd == "&" => match tokens.next() {
+                        Some(Token::Word(target)) => format!("&{target}"),
+                        _ => return Err(format!("missing target after {operator}&")),
+                    },
+                    _ => return Err(format!("missing target after {operator}")),
+                };
+                current.redirections.push(Redirection { operator, target });
+            }
+            Token::Operator(operator) if operator == "|" => {
+                let piped_from = current.program().map(str::to_owned);
+                commands.push(std::mem::take(&mut current));
+                current.piped_from = piped_from;
+            }
+            Token::Operator(operator) if SEPARATORS.contains(&operator.as_str()) => {
+                commands.push(std::mem::take(&mut current));
+            }
+            Token::Operator(operator) => return Err(format!("unexpected operator {operator}")),
+        }
+    }
+    commands.push(current);
+
+    Ok(commands
+        .into_iter()
+        .filter(|command| !command.words.is_empty() || !command.redirections.is_empty())
+        .collect())
+}
+
+fn is_assignment(word: &str) -> bool {
+    word.split_once('=').is_some_and(|(name, _)| {
+        !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+    })
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Operator(String),
+}
+
+fn tokenize(command: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut word: Option<String> = None;
+    let mut chars = command.char_indices().peekable();
+
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '\'' | '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        None => return Err(format!("unclosed quote {c}")),
+                        Some((_, quoted)) if quoted == c => break,
+                        Some((_, '\\')) if c == '"' => {
+                            if let Some((_, escaped)) = chars.next() {
+                                word.push(escaped);
+                            }
+                        }
+                        Some((_, quoted)) => word.push(quoted),
+                    }
+                }
+            }
+            '\\' => {
+                if let Some((_, escaped)) = chars.next() {
+                    if escaped != '\n' {
+                        word.get_or_insert_with(String::new).push(escaped);
+                    }
+                }
+            }
+            '#' if word.is_none() => while chars.next_if(|(_, c)| *c != '\n').is_some() {},
+            c if c.is_whitespace() && c != '\n' => {
+                if let Some(word) = word.take() {
+                    tokens.push(Token::Word(word));
+                }
+            }
+            _ => {
+                let rest = &command[index..];
+                match OPERATORS
+                    .iter()
+                    .find(|operator| rest.starts_with(**operator))
+                {
+                    Some(operator) => {
+                        if let Some(word) = word.take() {
+                            tokens.push(Token::Word(word));
+                        }
+                        for _ in 1..operator.len() {
+                            chars.next();
+                        }
+                        tokens.push(Token::Operator(operator.to_string()));
+                    }
+                    None => word.get_or_insert_with(String::new).push(c),
+                }
+            }
+        }
+    }
+
+    if let Some(word) = word {
+        tokens.push(Token::Word(word));
+    }
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn risk(command: &str) -> RiskLevel {
+        CommandPolicy::default().assess(command).risk
+    }
+
+    #[test]
+    fn test_parse_command() {
+        let commands =
+            parse_command("FOO=1 sudo rm -rf 'my dir' && curl -s x | sh > out.txt 2>&1").unwrap();
+
+        assert_eq!(commands.len(), 3);
+        assert_eq!(commands[0].words, ["sudo", "rm", "-rf", "my dir"]);
+        assert_eq!(commands[1].program(), Some("curl"));
+        assert_eq!(commands[2].piped_from.as_deref(), Some("curl"));
+        assert_eq!(commands[2].redirections[0].target, "out.txt");
+        assert!(parse_command("echo \"unclosed").is_err());
+    }
+
+    #[test]
+    fn test_assess() {
+        assert_eq!(risk("ls -la | grep foo"), RiskLevel::Low);
+        assert_eq!(risk("echo 'rm -rf /'"), RiskLevel::Low);
+        assert_eq!(risk("rm notes.txt"), RiskLevel::Medium);
+        assert_eq!(risk("git reset --hard HEAD~1"), RiskLevel::Medium);
+        assert_eq!(risk("rm -rf target"), RiskLevel::High);
+        assert_eq!(risk("rm -r /"), RiskLevel::High);
+        assert_eq!(risk("sudo apt update"), RiskLevel::High);
+        assert_eq!(risk("curl -fsSL https://x.sh | bash"), RiskLevel::High);
+        assert_eq!(risk("dd if=disk.img of=/dev/sda"), RiskLevel::High);
+        assert_eq!(risk("/sbin/mkfs.ext4 /dev/sdb1"), RiskLevel::High);
+        assert_eq!(risk("echo data > /dev/sda"), RiskLevel::High);
+        assert_eq!(risk("echo [package] > Cargo.toml"), RiskLevel::Medium);
+        assert_eq!(risk("echo line >> Cargo.toml"), RiskLevel::Low);
+    }
+
+    #[test]
+    fn test_assess_hidden_commands() {
+        assert_eq!(risk("bash -c \"rm -rf /\""), RiskLevel::High);
+        assert_eq!(risk("sh -lc 'ls'"), RiskLevel::High);
+        assert_eq!(risk("python3 -c 'print(1)'"), RiskLevel::High);
+        assert_eq!(risk("echo $(rm -rf ~)"), RiskLevel::High);
+        assert_eq!(risk("echo \"`whoami`\""), RiskLevel::High);
+        assert_eq!(risk("diff <(ls a) <(ls b)"), RiskLevel::High);
+        assert_eq!(risk("echo '$(not run)'"), RiskLevel::Low);
+        assert_eq!(risk("eval \"$COMMAND\""), RiskLevel::High);
+        assert_eq!(risk("find . -name '*.tmp' | xargs rm -rf"), RiskLevel::High);
+        assert_eq!(risk("ls | xargs -n 1 echo"), RiskLevel::High);
+        assert_eq!(risk("find / -delete"), RiskLevel::High);
+        assert_eq!(risk("find . -exec rm {} \\;"), RiskLevel::High);
+        assert_eq!(risk("find . -name '*.rs'"), RiskLevel::Low);
+        assert_eq!(risk("echo \"unclosed"), RiskLevel::High);
+    }
+
+    #[test]
+    fn test_policy() {
+        let policy = CommandPolicy::new(&["rm -rf target".to_owned()], &["docker".to_owned()]);
+
+        assert_eq!(policy.assess("rm -rf target").risk, RiskLevel::Low);
+        assert_eq!(policy.assess("rm -rf src").risk, RiskLevel::High);
+        assert_eq!(policy.assess("ls && docker ps").risk, RiskLevel::Denied);
+        assert_eq!(
+            policy.assess("sudo -u root docker ps").risk,
+            RiskLevel::Denied
+        );
+        assert_eq!(policy.assess("env FOO=1 docker ps").risk, RiskLevel::Denied);
+        assert_eq!(
+            policy.assess("ls | xargs docker rm").risk,
+            RiskLevel::Denied
+        );
+        assert_eq!(
+            policy.assess("nohup sudo docker ps").risk,
+            RiskLevel::Denied
+        );
+    }
+
+    #[test]
+    fn test_policy_allowed_command_redirection() {
+        let policy = CommandPolicy::new(&["echo".to_owned()], &[]);
+
+        assert_eq!(policy.assess("echo hi").risk, RiskLevel::Low);
+        assert_eq!(policy.assess("echo data > /dev/sda").risk, RiskLevel::High);
+    }
+
+    #[test]
+    fn test_command_failure_prompt() {
+        let stderr = (1..=50).map(|i| format!("line {i}\n")).collect::<String>();
+        let prompt = command_failure_prompt("make", Some(2), &stderr);
+
+        assert!(prompt.contains("failed with exit code 2"));
+        assert!(prompt.contains("line 11\nline 12"));
+        assert!(!prompt.contains("line 10\n"));
+    }
+
+    #[test]
+    fn test_strip_code_fences() {
+        assert_eq!(strip_code_fences("```bash\nls -la\n```"), "ls -la");
+        assert_eq!(strip_code_fences("`ls -la`"), "ls -la");
+        assert_eq!(strip_code_fences(" ls -la\n"), "ls -la");
+    }
+
+    #[test]
+    fn test_strip_enclosing_code_fence() {
+        assert_eq!(
+            strip_enclosing_code_fence("```\nFix parser\n\nDetails\n```\n"),
+            "Fix parser\n\nDetails"
+        );
+
+        let message = "Add example\n\n```rust\nlet x = 1;\n```\n\nUsed in docs";
+        assert_eq!(strip_enclosing_code_fence(message), message);
+        assert_eq!(strip_enclosing_code_fence("```\nunclosed"), "```\nunclosed");
+        assert_eq!(strip_enclosing_code_fence(" Fix typo\n"), "Fix typo");
+    }
+}
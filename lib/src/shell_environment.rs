@@ -0,0 +1,239 @@
+use anyhow::Result;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const COMMON_TOOLS: [&str; 14] = [
+    "git", "docker", "podman", "kubectl", "helm", "jq", "curl", "wget", "python3", "node", "cargo",
+    "brew", "apt", "dnf",
+];
+const OS_RELEASE_PATH: &str = "/etc/os-release";
+
+/// Environment generated shell commands run in, described to the model so commands fit it
+#[derive(Debug)]
+pub struct ShellEnvironment {
+    shell: String,
+    os: String,
+    cwd: Option<String>,
+    tools: Vec<String>,
+}
+
+impl ShellEnvironment {
+    /// Configured shell takes precedence over the one detected from `$SHELL`
+    pub fn detect(configured_shell: Option<&str>) -> Self {
+        let cwd = env::current_dir()
+            .ok()
+            .map(|path| path.to_string_lossy().to_string());
+        let tools = COMMON_TOOLS
+            .iter()
+            .filter(|tool| is_installed(tool))
+            .map(|tool| tool.to_string())
+            .collect();
+
+        Self {
+            shell: user_shell(configured_shell),
+            os: os_description(),
+            cwd,
+            tools,
+        }
+    }
+
+    pub fn shell(&self) -> &str {
+        &self.shell
+    }
+
+    pub fn shell_name(&self) -> &str {
+        shell_name(&self.shell)
+    }
+
+    /// History file of the shell, `$HISTFILE` takes precedence when exported
+    pub fn history_path(&self) -> Option<PathBuf> {
+        let histfile = env::var_os("HISTFILE").map(PathBuf::from);
+        match self.shell_name() {
+            "fish" => dirs::data_dir().map(|dir| dir.join("fish").join("fish_history")),
+            "zsh" => histfile.or_else(|| dirs::home_dir().map(|dir| dir.join(".zsh_history"))),
+            "bash" | "sh" => {
+                histfile.or_else(|| dirs::home_dir().map(|dir| dir.join(".bash_history")))
+            }
+            _ => None,
+        }
+    }
+
+    /// Latest command from shell history file, skipping commands starting with `skip_prefix`
+    pub fn last_history_command(&self, skip_prefix: &str) -> Result<Option<String>> {
+        let Some(path) = self.history_path().filter(|path| path.exists()) else {
+            return Ok(None);
+        };
+
+        let content = String::from_utf8_lossy(&fs::read(path)?).to_string();
+        let command = parse_history(self.shell_name(), &content)
+            .into_iter()
+            .rev()
+            .find(|command| !command.starts_with(skip_prefix));
+        Ok(command)
+    }
+
+    pub fn describe(&self) -> String {
+        let mut description = format!("The user runs {} on {}.", self.shell_name(), self.os);
+        if let Some(cwd) = &self.cwd {
+            description.push_str(&format!(" Current directory is {cwd}."));
+        }
+        match self.tools.is_empty() {
+            true => {
+                description.push_str(" None of the common tools like git or docker are installed.")
+            }
+            false => description.push_str(&format!(" Installed tools: {}.", self.tools.join(", "))),
+        }
+        description
+    }
+}
+
+/// Shell path or name from config, `$SHELL`, or the system default
+pub fn user_shell(configured_shell: Option<&str>) -> String {
+    if let Some(shell) = configured_shell.filter(|shell| !shell.trim().is_empty()) {
+        return shell.to_owned();
+    }
+
+    match env::consts::OS {
+        "windows" => "powershell".to_owned(),
+        os => env::var("SHELL")
+            .ok()
+            .filter(|shell| !shell.is_empty())
+            .unwrap_or_else(|| match os {
+                "macos" => "/bin/zsh".to_owned(),
+                _ => "/bin/sh".to_owned(),
+            }),
+    }
+}
+
+/// Command running given script with the shell, using the flag the shell expects
+pub fn shell_command(shell: &str, script: &str) -> Command {
+    let flag = match shell_name(shell) {
+        "powershell" | "pwsh" => "-Command",
+        "cmd" => "/C",
+        _ => "-c",
+    };
+
+    let mut command = Command::new(shell);
+    command.args([flag, script]);
+    command
+}
+
+fn shell_name(shell: &str) -> &str {
+    Path::new(shell)
+        .file_stem()
+        .and_then(|name| name.to_str())
+        .unwrap_or(shell)
+}
+
+fn os_description() -> String {
+    let os = env::consts::OS;
+    let release = match os {
+        "linux" => fs::read_to_string(OS_RELEASE_PATH)
+            .ok()
+            .and_then(|content| parse_os_release(&content)),
+        "macos" => Command::new("sw_vers")
+            .arg("-productVersion")
+            .output()
+            .ok()
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_owned())
+            .filter(|version| !version.is_empty())
+            .map(|version| format!("macOS {version}")),
+        _ => None,
+    };
+
+    release.unwrap_or_else(|| os.to_owned())
+}
+
+fn parse_os_release(content: &str) -> Option<String> {
+    let value = |key: &str| {
+        content.lines().find_map(|line| {
+            line.strip_prefix(key)?
+                .strip_prefix('=')
+                .map(|value| value.trim_matches('"').to_owned())
+        })
+    };
+
+    value("PRETTY_NAME").or_else(|| value("NAME"))
+}
+
+fn parse_history(shell_name: &str, content: &str) -> Vec<String> {
+    let commands = content.lines().filter_map(|line| match shell_name {
+        "fish" => line
+            .strip_prefix("- cmd: ")
+            .map(|command| command.replace("\\n", "\n")),
+        // extended history format is `: <timestamp>:<duration>;<command>`
+        "zsh" => match line.strip_prefix(": ") {
+            Some(extended) => extended
+                .split_once(';')
+                .map(|(_, command)| command.to_owned()),
+            None => Some(line.to_owned()),
+        },
+        // bash timestamps are comment lines like `#1700000000`
+        _ => match line.strip_prefix('#') {
+            Some(timestamp) if timestamp.chars().all(|c| c.is_ascii_digit()) => None,
+            _ => Some(line.to_owned()),
+        },
+    });
+
+    commands
+        .map(|command| command.trim().to_owned())
+        .filter(|command| !command.is_empty())
+        .collect()
+}
+
+fn is_installed(tool: &str) -> bool {
+    let Some(paths) = env::var_os("PATH") else {
+        return false;
+    };
+
+    env::split_paths(&paths).any(|dir| {
+        dir.join(tool).is_file() || (cfg!(windows) && dir.join(format!("{tool}.exe")).is_file())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_os_release() {
+        let content = "NAME=\"Ubuntu\"\nVERSION_ID=\"22.04\"\nPRETTY_NAME=\"Ubuntu 22.04.4 LTS\"\n";
+
+        assert_eq!(
+            parse_os_release(content).as_deref(),
+            Some("Ubuntu 22.04.4 LTS")
+        );
+        assert_eq!(parse_os_release("NAME=Alpine").as_deref(), Some("Alpine"));
+        assert_eq!(parse_os_release(""), None);
+    }
+
+    #[test]
+    fn test_parse_history() {
+        let zsh = ": 1700000000:0;ls -la\n: 1700000001:0;git status\n";
+        let fish = "- cmd: ls -la\n  when: 1700000000\n- cmd: git status\n  when: 1700000001\n";
+        let bash = "#1700000000\nls -la\n#1700000001\ngit status\n";
+
+        for (shell, content) in [("zsh", zsh), ("fish", fish), ("bash", bash)] {
+            assert_eq!(parse_history(shell, content), ["ls -la", "git status"]);
+        }
+    }
+
+    #[test]
+    fn test_describe() {
+        let environment = ShellEnvironment {
+            shell: "/usr/bin/fish".to_owned(),
+            os: "Fedora Linux 39".to_owned(),
+            cwd: Some("/home/user".to_owned()),
+            tools: vec!["git".to_owned(), "jq".to_owned()],
+        };
+
+        assert_eq!(environment.shell_name(), "fish");
+        assert_eq!(
+            environment.describe(),
+            "The user runs fish on Fedora Linux 39. Current directory is /home/user. Installed tools: git, jq."
+        );
+        assert_eq!(shell_name("powershell.exe"), "powershell");
+    }
+}
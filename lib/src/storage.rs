@@ -7,6 +7,17 @@ pub trait KVStorage<K, V>: Storage<V> {
     fn add(&self, key: K, value: V) -> anyhow::Result<()>;
     fn get(&self, key: K) -> anyhow::Result<Option<V>>;
     fn get_all(&self) -> anyhow::Result<Vec<V>>;
+    fn get_all_entries(&self) -> anyhow::Result<Vec<(K, V)>>;
     fn update(&self, key: K, value: V) -> anyhow::Result<()>;
     fn delete(&self, key: K) -> anyhow::Result<()>;
+
+    /// Update of a key the caller added itself, storages can skip checking that it exists
+    fn replace(&self, key: K, value: V) -> anyhow::Result<()> {
+        self.update(key, value)
+    }
+
+    /// Drops outdated values of the key, for storages keeping them until compacted
+    fn compact_key(&self, _key: K) -> anyhow::Result<()> {
+        Ok(())
+    }
 }
@@ -0,0 +1,345 @@
+use crate::{KVStorage, TokenUsage};
+use anyhow::{bail, Result};
+use chrono::{Datelike, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+
+/// USD per million prompt and completion tokens, longer prefixes win over shorter ones
+const MODEL_PRICES: &[(&str, f64, f64)] = &[
+    ("gpt-4o-mini", 0.15, 0.6),
+    ("gpt-4o", 2.5, 10.0),
+    ("gpt-4.1-nano", 0.1, 0.4),
+    ("gpt-4.1-mini", 0.4, 1.6),
+    ("gpt-4.1", 2.0, 8.0),
+    ("gpt-4-turbo", 10.0, 30.0),
+    ("gpt-4-32k", 60.0, 120.0),
+    ("gpt-4", 30.0, 60.0),
+    ("gpt-3.5-turbo", 0.5, 1.5),
+    ("o1-mini", 1.1, 4.4),
+    ("o1", 15.0, 60.0),
+    ("o3-mini", 1.1, 4.4),
+    ("o3", 2.0, 8.0),
+    ("o4-mini", 1.1, 4.4),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModelPrice {
+    pub prompt_per_million: f64,
+    pub completion_per_million: f64,
+}
+
+impl ModelPrice {
+    pub fn cost(&self, usage: &TokenUsage) -> f64 {
+        (usage.prompt_tokens as f64 * self.prompt_per_million
+            + usage.completion_tokens as f64 * self.completion_per_million)
+            / 1_000_000.0
+    }
+}
+
+/// Price of model, also for dated snapshots like `gpt-4o-2024-08-06`
+pub fn model_price(model: &str) -> Option<ModelPrice> {
+    MODEL_PRICES
+        .iter()
+        .filter(|(prefix, _, _)| model.starts_with(prefix))
+        .max_by_key(|(prefix, _, _)| prefix.len())
+        .map(|(_, prompt, completion)| ModelPrice {
+            prompt_per_million: *prompt,
+            completion_per_million: *completion,
+        })
+}
+
+/// Tokens used by one assistant with one model on one day
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UsageRecord {
+    pub date: NaiveDate,
+    pub assistant: String,
+    pub model: String,
+    pub usage: TokenUsage,
+    /// Cost in USD by the prices at the time of use, zero for models missing in price table
+    pub cost: f64,
+}
+
+impl UsageRecord {
+    fn key(&self) -> String {
+        format!("{}/{}/{}", self.date, self.model, self.assistant)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UsageGroup {
+    Day,
+    Model,
+    Assistant,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct UsageSummary {
+    pub group: String,
+    pub usage: TokenUsage,
+    pub cost: f64,
+}
+
+/// Totals per day, model or assistant, sorted by group
+pub fn summarize_usage(records: &[UsageRecord], group: UsageGroup) -> Vec<UsageSummary> {
+    let mut summaries: BTreeMap<String, UsageSummary> = BTreeMap::new();
+    for record in records {
+        let name = match group {
+            UsageGroup::Day => record.date.to_string(),
+            UsageGroup::Model => record.model.to_owned(),
+            UsageGroup::Assistant => record.assistant.to_owned(),
+        };
+        let summary = summaries
+            .entry(name.to_owned())
+            .or_insert_with(|| UsageSummary {
+                group: name,
+                usage: TokenUsage::default(),
+                cost: 0.0,
+            });
+        summary
+            .usage
+            .add(record.usage.prompt_tokens, record.usage.completion_tokens);
+        summary.cost += record.cost;
+    }
+
+    summaries.into_values().collect()
+}
+
+/// Spending limits in USD, soft ones only warn while hard ones refuse completions
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct Budgets {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub daily_soft: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub daily_hard: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monthly_soft: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monthly_hard: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Spending {
+    pub today: f64,
+    pub this_month: f64,
+}
+
+impl Budgets {
+    /// Error when a hard budget is used up, warning when a soft one is
+    pub fn check(&self, spending: &Spending) -> Result<Option<String>> {
+        let periods = [
+            ("daily", spending.today, self.daily_soft, self.daily_hard),
+            (
+                "monthly",
+                spending.this_month,
+                self.monthly_soft,
+                self.monthly_hard,
+            ),
+        ];
+
+        for (period, spent, _, hard) in periods {
+            if let Some(hard) = hard.filter(|hard| spent >= *hard) {
+                bail!("{period} budget of ${hard:.2} is used up (${spent:.2} spent), completions are refused");
+            }
+        }
+
+        let warnings: Vec<_> = periods
+            .into_iter()
+            .filter_map(|(period, spent, soft, _)| {
+                let soft = soft.filter(|soft| spent >= *soft)?;
+                Some(format!(
+                    "{period} budget of ${soft:.2} exceeded (${spent:.2} spent)"
+                ))
+            })
+            .collect();
+
+        match warnings.is_empty() {
+            true => Ok(None),
+            false => Ok(Some(format!("Warning: {}", warnings.join(", ")))),
+        }
+    }
+}
+
+impl Display for Spending {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "today: ${:.4}, this month: ${:.4}",
+            self.today, self.this_month
+        )
+    }
+}
+
+/// Records token usage of completions and keeps spending within budgets
+pub trait UsageTracker {
+    /// Checks budgets before a completion, `Ok(Some)` is a warning to show
+    fn check_budgets(&self) -> Result<Option<String>>;
+    fn track(&self, assistant: &str, model: &str, usage: &TokenUsage) -> Result<()>;
+}
+
+pub struct UsageStorage<S>
+where
+    S: KVStorage<String, UsageRecord>,
+{
+    storage: S,
+    budgets: Budgets,
+}
+
+impl<S> UsageStorage<S>
+where
+    S: KVStorage<String, UsageRecord>,
+{
+    pub fn new(storage: S) -> Self {
+        Self {
+            storage,
+            budgets: Budgets::default(),
+        }
+    }
+
+    pub fn with_budgets(mut self, budgets: Budgets) -> Self {
+        self.budgets = budgets;
+        self
+    }
+
+    /// Records of the last `days` days including today
+    pub fn list(&self, days: u32) -> Result<Vec<UsageRecord>> {
+        let since = Local::now().date_naive() - chrono::Days::new(days.saturating_sub(1).into());
+        let mut records: Vec<_> = self
+            .storage
+            .get_all()?
+            .into_iter()
+            .filter(|record| record.date >= since)
+            .collect();
+        records.sort_by_key(UsageRecord::key);
+        Ok(records)
+    }
+
+    pub fn spending(&self) -> Result<Spending> {
+        let today = Local::now().date_naive();
+        let mut spending = Spending {
+            today: 0.0,
+            this_month: 0.0,
+        };
+
+        for record in self.storage.get_all()? {
+            if record.date == today {
+                spending.today += record.cost;
+            }
+            if (record.date.year(), record.date.month()) == (today.year(), today.month()) {
+                spending.this_month += record.cost;
+            }
+        }
+        Ok(spending)
+    }
+}
+
+impl<S> UsageTracker for UsageStorage<S>
+where
+    S: KVStorage<String, UsageRecord>,
+{
+    fn check_budgets(&self) -> Result<Option<String>> {
+        if self.budgets == Budgets::default() {
+            return Ok(None);
+        }
+        self.budgets.check(&self.spending()?)
+    }
+
+    fn track(&self, assistant: &str, model: &str, usage: &TokenUsage) -> Result<()> {
+        let cost = model_price(model).map_or(0.0, |price| price.cost(usage));
+        let mut record = UsageRecord {
+            date: Local::now().date_naive(),
+            assistant: assistant.to_owned(),
+            model: model.to_owned(),
+            usage: *usage,
+            cost,
+        };
+        let key = record.key();
+
+        match self.storage.get(key.to_owned())? {
+            Some(existing) => {
+                record.usage.add(
+                    existing.usage.prompt_tokens,
+                    existing.usage.completion_tokens,
+                );
+                record.cost += existing.cost;
+                self.storage.update(key, record)
+            }
+            None => self.storage.add(key, record),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_model_price() {
+        let price = model_price("gpt-4o-mini-2024-07-18").unwrap();
+        assert_eq!(price.prompt_per_million, 0.15);
+
+        let usage = TokenUsage {
+            prompt_tokens: 1_000_000,
+            completion_tokens: 500_000,
+        };
+        assert_eq!(model_price("gpt-4o-2024-08-06").unwrap().cost(&usage), 7.5);
+        assert_eq!(model_price("davinci-002"), None);
+    }
+
+    #[test]
+    fn test_budgets() -> Result<()> {
+        let budgets = Budgets {
+            daily_soft: Some(1.0),
+            monthly_hard: Some(10.0),
+            ..Default::default()
+        };
+
+        let within = Spending {
+            today: 0.5,
+            this_month: 5.0,
+        };
+        assert_eq!(budgets.check(&within)?, None);
+
+        let soft = Spending {
+            today: 1.5,
+            this_month: 5.0,
+        };
+        assert!(budgets.check(&soft)?.unwrap().contains("daily budget"));
+
+        let hard = Spending {
+            today: 1.5,
+            this_month: 10.0,
+        };
+        assert!(budgets.check(&hard).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_summarize_usage() {
+        let record = |date: &str, model: &str, tokens: u32, cost: f64| UsageRecord {
+            date: date.parse().unwrap(),
+            assistant: "default".to_owned(),
+            model: model.to_owned(),
+            usage: TokenUsage {
+                prompt_tokens: tokens,
+                completion_tokens: tokens,
+            },
+            cost,
+        };
+        let records = [
+            record("2024-03-01", "gpt-4o", 10, 1.0),
+            record("2024-03-02", "gpt-4o-mini", 20, 0.5),
+            record("2024-03-02", "gpt-4o", 30, 2.0),
+        ];
+
+        let by_model = summarize_usage(&records, UsageGroup::Model);
+        assert_eq!(by_model.len(), 2);
+        assert_eq!(by_model[0].group, "gpt-4o");
+        assert_eq!(by_model[0].usage.prompt_tokens, 40);
+        assert_eq!(by_model[0].cost, 3.0);
+
+        let by_day = summarize_usage(&records, UsageGroup::Day);
+        assert_eq!(by_day[1].group, "2024-03-02");
+        assert_eq!(by_day[1].cost, 2.5);
+    }
+}
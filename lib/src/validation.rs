@@ -1,7 +1,7 @@
 use crate::OpenAi;
 use anyhow::{anyhow, bail};
 
-pub fn openai_api_key_format_validator(api_key: &String) -> anyhow::Result<()> {
+pub fn openai_api_key_format_validator(api_key: &str) -> anyhow::Result<()> {
     let re = regex::Regex::new(r"^sk-[0-9a-f]{32}$").unwrap();
     if !re.is_match(api_key) {
         bail!("Invalid format for OpenAi API key")
@@ -0,0 +1,100 @@
+use crate::{LocalJsonStorage, LocalJsonlStorage, SqliteStorage};
+use lib::{KVStorage, Storage};
+use serde::{Deserialize, Serialize};
+
+/// Storage chosen by the `storage_backend` config option
+pub enum BackendStorage {
+    Json(LocalJsonStorage),
+    Jsonl(LocalJsonlStorage),
+    Sqlite(SqliteStorage),
+}
+
+impl<T> Storage<T> for BackendStorage
+where
+    T: Serialize + for<'d> Deserialize<'d>,
+{
+    fn write(&self, item: &T) -> anyhow::Result<()> {
+        match self {
+            BackendStorage::Json(storage) => storage.write(item),
+            BackendStorage::Jsonl(storage) => storage.write(item),
+            BackendStorage::Sqlite(storage) => storage.write(item),
+        }
+    }
+
+    fn read(&self) -> anyhow::Result<Option<T>> {
+        match self {
+            BackendStorage::Json(storage) => storage.read(),
+            BackendStorage::Jsonl(storage) => storage.read(),
+            BackendStorage::Sqlite(storage) => storage.read(),
+        }
+    }
+}
+
+impl<V> KVStorage<String, V> for BackendStorage
+where
+    V: Serialize + for<'d> Deserialize<'d>,
+{
+    fn add(&self, key: String, value: V) -> anyhow::Result<()> {
+        match self {
+            BackendStorage::Json(storage) => storage.add(key, value),
+            BackendStorage::Jsonl(storage) => storage.add(key, value),
+            BackendStorage::Sqlite(storage) => storage.add(key, value),
+        }
+    }
+
+    fn get(&self, key: String) -> anyhow::Result<Option<V>> {
+        match self {
+            BackendStorage::Json(storage) => storage.get(key),
+            BackendStorage::Jsonl(storage) => storage.get(key),
+            BackendStorage::Sqlite(storage) => storage.get(key),
+        }
+    }
+
+    fn get_all(&self) -> anyhow::Result<Vec<V>> {
+        match self {
+            BackendStorage::Json(storage) => storage.get_all(),
+            BackendStorage::Jsonl(storage) => storage.get_all(),
+            BackendStorage::Sqlite(storage) => storage.get_all(),
+        }
+    }
+
+    fn get_all_entries(&self) -> anyhow::Result<Vec<(String, V)>> {
+        match self {
+            BackendStorage::Json(storage) => storage.get_all_entries(),
+            BackendStorage::Jsonl(storage) => storage.get_all_entries(),
+            BackendStorage::Sqlite(storage) => storage.get_all_entries(),
+        }
+    }
+
+    fn update(&self, key: String, value: V) -> anyhow::Result<()> {
+        match self {
+            BackendStorage::Json(storage) => storage.update(key, value),
+            BackendStorage::Jsonl(storage) => storage.update(key, value),
+            BackendStorage::Sqlite(storage) => storage.update(key, value),
+        }
+    }
+
+    fn replace(&self, key: String, value: V) -> anyhow::Result<()> {
+        match self {
+            BackendStorage::Json(storage) => storage.replace(key, value),
+            BackendStorage::Jsonl(storage) => storage.replace(key, value),
+            BackendStorage::Sqlite(storage) => storage.replace(key, value),
+        }
+    }
+
+    fn compact_key(&self, key: String) -> anyhow::Result<()> {
+        match self {
+            BackendStorage::Json(storage) => KVStorage::<String, V>::compact_key(storage, key),
+            BackendStorage::Jsonl(storage) => KVStorage::<String, V>::compact_key(storage, key),
+            BackendStorage::Sqlite(storage) => KVStorage::<String, V>::compact_key(storage, key),
+        }
+    }
+
+    fn delete(&self, key: String) -> anyhow::Result<()> {
+        match self {
+            BackendStorage::Json(storage) => KVStorage::<String, V>::delete(storage, key),
+            BackendStorage::Jsonl(storage) => KVStorage::<String, V>::delete(storage, key),
+            BackendStorage::Sqlite(storage) => KVStorage::<String, V>::delete(storage, key),
+        }
+    }
+}
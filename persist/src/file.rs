@@ -0,0 +1,105 @@
+use anyhow::Context;
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+#[cfg(unix)]
+const PRIVATE_MODE: u32 = 0o600;
+
+/// Exclusive advisory lock held until returned file is dropped, guards read-modify-write cycles
+/// against other explice processes
+pub(crate) fn lock(path: &Path) -> anyhow::Result<File> {
+    create_parent_dir(path)?;
+    let file = File::create(sibling_path(path, "lock"))?;
+    file.lock()
+        .with_context(|| format!("failed to lock {path:?}"))?;
+
+    Ok(file)
+}
+
+pub(crate) fn create_parent_dir(path: &Path) -> anyhow::Result<()> {
+    let dir = path.parent().unwrap();
+    if !dir.try_exists()? {
+        fs::create_dir_all(dir)?;
+    }
+    Ok(())
+}
+
+/// Path next to the storage file with extra extension, e.g. `chat_records.json.bak`
+pub(crate) fn sibling_path(path: &Path, extension: &str) -> PathBuf {
+    let mut path = OsString::from(path);
+    path.push(".");
+    path.push(extension);
+    PathBuf::from(path)
+}
+
+/// Writes to temporary file renamed over the target, so it's never left half written.
+/// Private file is readable only by its owner from the moment it's created
+pub(crate) fn write_atomic(path: &Path, content: &[u8], private: bool) -> anyhow::Result<()> {
+    create_parent_dir(path)?;
+
+    let temp_path = sibling_path(path, "tmp");
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    if private {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(PRIVATE_MODE);
+    }
+    let mut temp_file = options.open(&temp_path)?;
+    #[cfg(unix)]
+    if private {
+        // mode applies only to new files, temporary one may be left from a crash
+        restrict_permissions(&temp_path)?;
+    }
+    temp_file.write_all(content)?;
+    temp_file.sync_all()?;
+
+    fs::rename(&temp_path, path).map_err(anyhow::Error::from)
+}
+
+/// Makes file readable and writable only by its owner, no-op where permissions are not Unix ones
+pub(crate) fn restrict_permissions(path: &Path) -> anyhow::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let permissions = fs::metadata(path)?.permissions();
+        if permissions.mode() & 0o777 != PRIVATE_MODE {
+            fs::set_permissions(path, fs::Permissions::from_mode(PRIVATE_MODE))?;
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_private_write_atomic() -> anyhow::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = std::env::temp_dir().join(format!("explice-file-test-{}", ulid::Ulid::new()));
+        let path = dir.join("config.json");
+        let mode = |path: &Path| -> anyhow::Result<u32> {
+            Ok(fs::metadata(path)?.permissions().mode() & 0o777)
+        };
+
+        write_atomic(&path, b"{}", true)?;
+        assert_eq!(mode(&path)?, PRIVATE_MODE);
+
+        // temporary file left from a crash keeps its mode when reopened
+        let temp_path = sibling_path(&path, "tmp");
+        fs::write(&temp_path, "")?;
+        fs::set_permissions(&temp_path, fs::Permissions::from_mode(0o644))?;
+        write_atomic(&path, b"{\"a\": 1}", true)?;
+        assert_eq!(mode(&path)?, PRIVATE_MODE);
+        assert_eq!(fs::read_to_string(&path)?, "{\"a\": 1}");
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,360 @@
+use crate::file::{create_parent_dir, lock, write_atomic};
+use anyhow::bail;
+use lib::{KVStorage, Storage};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+/// Line of the file, entry without value is a tombstone of deleted key
+#[derive(Serialize, Deserialize)]
+struct Entry<V> {
+    key: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value: Option<V>,
+}
+
+/// Key of a line, without reading its value
+#[derive(Deserialize)]
+struct EntryKey {
+    key: String,
+}
+
+/// Append-only storage with one JSON entry per line, updates and deletes append entries
+/// shadowing the older ones until the file is compacted.
+///
+/// `add` does not read the file, so keys are expected to be unique like ULIDs of chat records.
+pub struct LocalJsonlStorage {
+    path: PathBuf,
+}
+
+#[derive(Debug)]
+pub struct CompactionStats {
+    pub lines_before: usize,
+    pub lines_after: usize,
+}
+
+impl LocalJsonlStorage {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub fn exists(&self) -> anyhow::Result<bool> {
+        self.path.try_exists().map_err(anyhow::Error::from)
+    }
+
+    /// Live values with their keys in the order they were first added
+    pub fn entries<V>(&self) -> anyhow::Result<Vec<(String, V)>>
+    where
+        V: Serialize + for<'d> Deserialize<'d>,
+    {
+        let mut entries: Vec<Option<(String, V)>> = vec![];
+        let mut positions: HashMap<String, usize> = HashMap::new();
+
+        self.for_each_entry(|entry: Entry<V>| {
+            match (entry.value, positions.get(&entry.key)) {
+                (Some(value), Some(&position)) => entries[position] = Some((entry.key, value)),
+                (Some(value), None) => {
+                    positions.insert(entry.key.to_owned(), entries.len());
+                    entries.push(Some((entry.key, value)));
+                }
+                (None, Some(&position)) => {
+                    entries[position] = None;
+                    positions.remove(&entry.key);
+                }
+                (None, None) => {}
+            }
+            true
+        })?;
+
+        Ok(entries.into_iter().flatten().collect())
+    }
+
+    /// Appends entries with keys missing from the file, returns how many were appended
+    pub fn import<V>(&self, entries: Vec<(String, V)>) -> anyhow::Result<usize>
+    where
+        V: Serialize + for<'d> Deserialize<'d>,
+    {
+        let _lock = lock(&self.path)?;
+        let entries_in_file: Vec<(String, serde_json::Value)> = self.entries()?;
+        let mut existing: HashSet<String> =
+            entries_in_file.into_iter().map(|(key, _)| key).collect();
+        let lines = entries
+            .into_iter()
+            .filter(|(key, _)| existing.insert(key.to_owned()))
+            .map(|(key, value)| to_line(key, Some(value)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        self.append(&lines.concat())?;
+        Ok(lines.len())
+    }
+
+    /// Rewrites the file without shadowed entries and tombstones
+    pub fn compact(&self) -> anyhow::Result<CompactionStats> {
+        let _lock = lock(&self.path)?;
+        let mut lines_before = 0;
+        self.for_each_entry(|_: Entry<serde_json::Value>| {
+            lines_before += 1;
+            true
+        })?;
+
+        let entries: Vec<(String, serde_json::Value)> = self.entries()?;
+        let lines_after = entries.len();
+        let content = entries
+            .into_iter()
+            .map(|(key, value)| to_line(key, Some(value)))
+            .collect::<anyhow::Result<String>>()?;
+        write_atomic(&self.path, content.as_bytes(), false)?;
+
+        Ok(CompactionStats {
+            lines_before,
+            lines_after,
+        })
+    }
+
+    /// Streams entries from the file until `f` returns false, malformed lines are skipped
+    fn for_each_entry<V, F>(&self, mut f: F) -> anyhow::Result<()>
+    where
+        V: for<'d> Deserialize<'d>,
+        F: FnMut(Entry<V>) -> bool,
+    {
+        if !self.exists()? {
+            return Ok(());
+        }
+
+        let reader = BufReader::new(File::open(&self.path)?);
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(&line) {
+                Ok(entry) => {
+                    if !f(entry) {
+                        break;
+                    }
+                }
+                Err(err) => eprintln!(
+                    "skipping malformed line {} of {:?}: {err}",
+                    index + 1,
+                    self.path
+                ),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn contains(&self, key: &str) -> anyhow::Result<bool> {
+        let mut exists = false;
+        self.for_each_entry(|entry: Entry<serde_json::Value>| {
+            if entry.key == key {
+                exists = entry.value.is_some();
+            }
+            true
+        })?;
+        Ok(exists)
+    }
+
+    fn append(&self, lines: &str) -> anyhow::Result<()> {
+        if lines.is_empty() {
+            return Ok(());
+        }
+
+        create_parent_dir(&self.path)?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&self.path)?;
+
+        // line cut short by a crash must not swallow the appended one
+        let mut content = String::new();
+        if file.metadata()?.len() > 0 {
+            let mut last = [0; 1];
+            file.seek(SeekFrom::End(-1))?;
+            file.read_exact(&mut last)?;
+            if last[0] != b'\n' {
+                content.push('\n');
+            }
+        }
+        content.push_str(lines);
+
+        file.write_all(content.as_bytes())?;
+        file.sync_data().map_err(anyhow::Error::from)
+    }
+}
+
+impl<T> Storage<T> for LocalJsonlStorage
+where
+    T: Serialize + for<'d> Deserialize<'d>,
+{
+    fn write(&self, _item: &T) -> anyhow::Result<()> {
+        bail!(
+            "{:?} keeps separate entries, it can't be written as a whole",
+            self.path
+        )
+    }
+
+    fn read(&self) -> anyhow::Result<Option<T>> {
+        bail!(
+            "{:?} keeps separate entries, it can't be read as a whole",
+            self.path
+        )
+    }
+}
+
+impl<V> KVStorage<String, V> for LocalJsonlStorage
+where
+    V: Serialize + for<'d> Deserialize<'d>,
+{
+    fn add(&self, key: String, value: V) -> anyhow::Result<()> {
+        let _lock = lock(&self.path)?;
+        self.append(&to_line(key, Some(value))?)
+    }
+
+    fn get(&self, key: String) -> anyhow::Result<Option<V>> {
+        let mut value = None;
+        self.for_each_entry(|entry: Entry<V>| {
+            if entry.key == key {
+                value = entry.value;
+            }
+            true
+        })?;
+        Ok(value)
+    }
+
+    fn get_all(&self) -> anyhow::Result<Vec<V>> {
+        let entries = self.entries()?;
+        Ok(entries.into_iter().map(|(_, value)| value).collect())
+    }
+
+    fn get_all_entries(&self) -> anyhow::Result<Vec<(String, V)>> {
+        self.entries()
+    }
+
+    fn update(&self, key: String, value: V) -> anyhow::Result<()> {
+        let _lock = lock(&self.path)?;
+        if !self.contains(&key)? {
+            bail!("key \"{key}\" does not exist");
+        }
+
+        self.append(&to_line(key, Some(value))?)
+    }
+
+    /// Appends without scanning the file for the key
+    fn replace(&self, key: String, value: V) -> anyhow::Result<()> {
+        let _lock = lock(&self.path)?;
+        self.append(&to_line(key, Some(value))?)
+    }
+
+    fn delete(&self, key: String) -> anyhow::Result<()> {
+        let _lock = lock(&self.path)?;
+        if !self.contains(&key)? {
+            bail!("key \"{key}\" does not exist");
+        }
+
+        self.append(&to_line::<V>(key, None)?)
+    }
+
+    /// Keeps only the latest line of the key, in place of its first one
+    fn compact_key(&self, key: String) -> anyhow::Result<()> {
+        let _lock = lock(&self.path)?;
+        if !self.exists()? {
+            return Ok(());
+        }
+
+        let content = fs::read_to_string(&self.path)?;
+        let lines: Vec<&str> = content.lines().collect();
+        let key_lines: Vec<usize> = lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| {
+                serde_json::from_str::<EntryKey>(line).is_ok_and(|entry| entry.key == key)
+            })
+            .map(|(index, _)| index)
+            .collect();
+        let (Some(&first), Some(&last)) = (key_lines.first(), key_lines.last()) else {
+            return Ok(());
+        };
+        if first == last {
+            return Ok(());
+        }
+
+        let content: String = lines
+            .iter()
+            .enumerate()
+            .filter_map(|(index, line)| match index {
+                index if index == first => Some(lines[last]),
+                index if key_lines.binary_search(&index).is_ok() => None,
+                _ => Some(*line),
+            })
+            .map(|line| format!("{line}\n"))
+            .collect();
+        write_atomic(&self.path, content.as_bytes(), false)
+    }
+}
+
+fn to_line<V: Serialize>(key: String, value: Option<V>) -> anyhow::Result<String> {
+    let mut line = serde_json::to_string(&Entry { key, value })?;
+    line.push('\n');
+    Ok(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_append_delete_compact() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join(format!("explice-jsonl-test-{}", ulid::Ulid::new()));
+        let path = dir.join("records.jsonl");
+        let storage = LocalJsonlStorage::new(path.to_owned());
+
+        storage.add("a".to_owned(), 1)?;
+        storage.add("b".to_owned(), 2)?;
+        storage.update("a".to_owned(), 3)?;
+        storage.replace("b".to_owned(), 5)?;
+        assert_eq!(storage.get("b".to_owned())?, Some(5));
+        KVStorage::<String, i32>::delete(&storage, "b".to_owned())?;
+        assert!(KVStorage::<String, i32>::delete(&storage, "b".to_owned()).is_err());
+
+        // crash in the middle of writing a line
+        fs::write(&path, fs::read_to_string(&path)? + "{\"key\": \"c\", \"val")?;
+        storage.add("d".to_owned(), 4)?;
+
+        let values: Vec<i32> = storage.get_all()?;
+        assert_eq!(values, [3, 4]);
+        assert_eq!(storage.get("b".to_owned())?, None::<i32>);
+
+        let stats = storage.compact()?;
+        assert_eq!((stats.lines_before, stats.lines_after), (6, 2));
+        let values: Vec<i32> = storage.get_all()?;
+        assert_eq!(values, [3, 4]);
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_compact_key() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join(format!("explice-jsonl-test-{}", ulid::Ulid::new()));
+        let path = dir.join("records.jsonl");
+        let storage = LocalJsonlStorage::new(path.to_owned());
+
+        storage.add("a".to_owned(), 1)?;
+        storage.add("b".to_owned(), 2)?;
+        storage.replace("a".to_owned(), 3)?;
+        storage.replace("a".to_owned(), 4)?;
+        KVStorage::<String, i32>::compact_key(&storage, "a".to_owned())?;
+
+        assert_eq!(fs::read_to_string(&path)?.lines().count(), 2);
+        let entries: Vec<(String, i32)> = storage.get_all_entries()?;
+        assert_eq!(entries, [("a".to_owned(), 4), ("b".to_owned(), 2)]);
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -1,3 +1,10 @@
+mod backend;
+mod file;
+mod jsonl;
 mod local;
+mod sqlite;
 
+pub use backend::BackendStorage;
+pub use jsonl::{CompactionStats, LocalJsonlStorage};
 pub use local::LocalJsonStorage;
+pub use sqlite::{SqliteStorage, SqliteTable};
@@ -1,3 +1,4 @@
+use crate::file::{lock, restrict_permissions, sibling_path, write_atomic};
 use anyhow::bail;
 use lib::{KVStorage, Storage};
 use serde::{Deserialize, Serialize};
@@ -7,11 +8,32 @@ use std::path::PathBuf;
 
 pub struct LocalJsonStorage {
     path: PathBuf,
+    private: bool,
 }
 
 impl LocalJsonStorage {
     pub fn new(path: PathBuf) -> Self {
-        Self { path }
+        Self {
+            path,
+            private: false,
+        }
+    }
+
+    /// File readable only by its owner, permissions of existing file are fixed on next read
+    pub fn with_private_permissions(mut self) -> Self {
+        self.private = true;
+        self
+    }
+
+    /// Values with their keys sorted by key
+    pub fn entries<V>(&self) -> anyhow::Result<Vec<(String, V)>>
+    where
+        V: Serialize + for<'d> Deserialize<'d>,
+    {
+        let content: HashMap<String, V> = self.read()?.unwrap_or_default();
+        let mut entries: Vec<_> = content.into_iter().collect();
+        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+        Ok(entries)
     }
 }
 
@@ -20,24 +42,33 @@ where
     T: Serialize + for<'d> Deserialize<'d>,
 {
     fn write(&self, item: &T) -> anyhow::Result<()> {
-        let dir = &self.path.parent().unwrap();
-        if !dir.try_exists()? {
-            fs::create_dir_all(dir)?;
-        }
-
         let json = serde_json::to_string(&item)?;
-        fs::write(&self.path, json).map_err(anyhow::Error::from)
+        write_atomic(&self.path, json.as_bytes(), self.private)
     }
 
+    /// File with malformed JSON is moved to `.bak` and treated as empty, instead of failing every read
     fn read(&self) -> anyhow::Result<Option<T>> {
         if !&self.path.try_exists()? {
             return Ok(None);
         }
+        if self.private {
+            restrict_permissions(&self.path)?;
+        }
 
         let content = fs::read_to_string(&self.path)?;
-        let data = serde_json::from_str(&content)?;
-
-        Ok(Some(data))
+        match serde_json::from_str(&content) {
+            Ok(data) => Ok(Some(data)),
+            Err(err) if err.is_data() => Err(err.into()),
+            Err(err) => {
+                let backup_path = sibling_path(&self.path, "bak");
+                fs::rename(&self.path, &backup_path)?;
+                eprintln!(
+                    "{:?} is corrupted ({err}), it was moved to {backup_path:?}",
+                    self.path
+                );
+                Ok(None)
+            }
+        }
     }
 }
 
@@ -46,6 +77,7 @@ where
     V: Serialize + for<'d> Deserialize<'d>,
 {
     fn add(&self, key: String, value: V) -> anyhow::Result<()> {
+        let _lock = lock(&self.path)?;
         let mut content: HashMap<String, V> = self.read()?.unwrap_or_default();
 
         if content.contains_key(&key) {
@@ -75,7 +107,12 @@ where
         Ok(values)
     }
 
+    fn get_all_entries(&self) -> anyhow::Result<Vec<(String, V)>> {
+        self.entries()
+    }
+
     fn update(&self, key: String, value: V) -> anyhow::Result<()> {
+        let _lock = lock(&self.path)?;
         let mut content: HashMap<String, V> = self.read()?.unwrap_or_default();
 
         if !content.contains_key(&key) {
@@ -88,6 +125,7 @@ where
     }
 
     fn delete(&self, key: String) -> anyhow::Result<()> {
+        let _lock = lock(&self.path)?;
         let mut content: HashMap<String, V> = self.read()?.unwrap_or_default();
 
         if !content.contains_key(&key) {
@@ -99,3 +137,36 @@ where
         self.write(&content)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_concurrent_adds_and_recovery() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join(format!("explice-json-test-{}", ulid::Ulid::new()));
+        let path = dir.join("records.json");
+
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                let path = path.to_owned();
+                thread::spawn(move || LocalJsonStorage::new(path).add(i.to_string(), i))
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap()?;
+        }
+        let storage = LocalJsonStorage::new(path.to_owned());
+        let values: Vec<i32> = storage.get_all()?;
+        assert_eq!(values.len(), 8);
+
+        fs::write(&path, "{\"0\": 0, \"1\":")?;
+        let values: Vec<i32> = storage.get_all()?;
+        assert!(values.is_empty());
+        assert!(sibling_path(&path, "bak").exists());
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
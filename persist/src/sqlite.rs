@@ -0,0 +1,453 @@
+use anyhow::{bail, Context};
+use lib::{KVStorage, Storage};
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Creation date converted to UTC, so its text sorts chronologically across offsets,
+/// dates SQLite can't read are kept as they are
+const UTC_CREATION_DATE: &str = "coalesce(strftime('%Y-%m-%dT%H:%M:%fZ', ?3), ?3)";
+
+/// Schema changes applied in order, index of the last applied one is kept in `user_version`
+const MIGRATIONS: &[&str] = &[
+    r#"
+    CREATE TABLE chat_records (
+        key TEXT PRIMARY KEY,
+        assistant_name TEXT NOT NULL,
+        creation_date TEXT NOT NULL,
+        data TEXT NOT NULL
+    );
+    CREATE INDEX chat_records_creation_date ON chat_records (creation_date);
+    CREATE INDEX chat_records_assistant_name ON chat_records (assistant_name);
+
+    CREATE TABLE chat_messages (
+        record_key TEXT NOT NULL REFERENCES chat_records (key) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        role TEXT NOT NULL,
+        content TEXT NOT NULL,
+        data TEXT NOT NULL,
+        PRIMARY KEY (record_key, position)
+    );
+
+    CREATE TABLE assistants (
+        name TEXT PRIMARY KEY,
+        model TEXT NOT NULL,
+        data TEXT NOT NULL
+    );
+
+    CREATE TABLE key_values (
+        namespace TEXT NOT NULL,
+        key TEXT NOT NULL,
+        data TEXT NOT NULL,
+        PRIMARY KEY (namespace, key)
+    );
+
+    CREATE TABLE documents (
+        name TEXT PRIMARY KEY,
+        data TEXT NOT NULL
+    );
+"#,
+    r#"
+    UPDATE chat_records
+    SET creation_date = coalesce(strftime('%Y-%m-%dT%H:%M:%fZ', creation_date), creation_date);
+"#,
+];
+
+/// Table the storage keeps its values in
+#[derive(Debug, Clone)]
+pub enum SqliteTable {
+    /// Records with their messages split into an indexed table
+    ChatRecords,
+    Assistants,
+    /// Generic values, each namespace is a separate collection
+    KeyValues(String),
+}
+
+impl SqliteTable {
+    fn name(&self) -> &str {
+        match self {
+            SqliteTable::ChatRecords => "chat_records",
+            SqliteTable::Assistants => "assistants",
+            SqliteTable::KeyValues(namespace) => namespace,
+        }
+    }
+}
+
+pub struct SqliteStorage {
+    connection: Connection,
+    table: SqliteTable,
+}
+
+impl SqliteStorage {
+    pub fn open(path: PathBuf, table: SqliteTable) -> anyhow::Result<Self> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let connection =
+            Connection::open(&path).with_context(|| format!("failed to open database {path:?}"))?;
+        connection.busy_timeout(BUSY_TIMEOUT)?;
+        connection.pragma_update(None, "journal_mode", "WAL")?;
+        connection.pragma_update(None, "foreign_keys", "ON")?;
+        migrate(&connection)?;
+
+        Ok(Self { connection, table })
+    }
+
+    /// Inserts entries missing from the table, returns how many were inserted
+    pub fn import(&self, entries: Vec<(String, Value)>) -> anyhow::Result<usize> {
+        let transaction = self.connection.unchecked_transaction()?;
+        let mut imported = 0;
+        for (key, value) in entries {
+            if self.contains(&transaction, &key)? {
+                continue;
+            }
+            self.insert(&transaction, &key, value)?;
+            imported += 1;
+        }
+        transaction.commit()?;
+
+        Ok(imported)
+    }
+
+    fn contains(&self, connection: &Connection, key: &str) -> anyhow::Result<bool> {
+        let exists = match &self.table {
+            SqliteTable::ChatRecords => connection
+                .query_row("SELECT 1 FROM chat_records WHERE key = ?1", [key], |_| {
+                    Ok(())
+                })
+                .optional()?,
+            SqliteTable::Assistants => connection
+                .query_row(
+                    "SELECT 1 FROM assistants WHERE name = ?1",
+                    [key],
+                    |_| Ok(()),
+                )
+                .optional()?,
+            SqliteTable::KeyValues(namespace) => connection
+                .query_row(
+                    "SELECT 1 FROM key_values WHERE namespace = ?1 AND key = ?2",
+                    [namespace, key],
+                    |_| Ok(()),
+                )
+                .optional()?,
+        };
+        Ok(exists.is_some())
+    }
+
+    fn insert(&self, transaction: &Transaction, key: &str, value: Value) -> anyhow::Result<()> {
+        match &self.table {
+            SqliteTable::ChatRecords => {
+                let Value::Object(mut record) = value else {
+                    bail!("chat record must be a JSON object");
+                };
+                let messages = match record.remove("messages") {
+                    Some(Value::Array(messages)) => messages,
+                    _ => vec![],
+                };
+                let string_field = |name: &str| {
+                    record
+                        .get(name)
+                        .and_then(Value::as_str)
+                        .unwrap_or_default()
+                        .to_owned()
+                };
+
+                transaction.execute(
+                    &format!("INSERT INTO chat_records (key, assistant_name, creation_date, data) VALUES (?1, ?2, {UTC_CREATION_DATE}, ?4)"),
+                    params![
+                        key,
+                        string_field("assistant_name"),
+                        string_field("creation_date"),
+                        serde_json::to_string(&record)?
+                    ],
+                )?;
+                for (position, message) in messages.iter().enumerate() {
+                    let field = |name: &str| {
+                        message
+                            .get(name)
+                            .and_then(Value::as_str)
+                            .unwrap_or_default()
+                    };
+                    transaction.execute(
+                        "INSERT INTO chat_messages (record_key, position, role, content, data) VALUES (?1, ?2, ?3, ?4, ?5)",
+                        params![
+                            key,
+                            position as i64,
+                            field("role"),
+                            field("content"),
+                            serde_json::to_string(message)?
+                        ],
+                    )?;
+                }
+            }
+            SqliteTable::Assistants => {
+                let model = value
+                    .get("model")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default();
+                transaction.execute(
+                    "INSERT INTO assistants (name, model, data) VALUES (?1, ?2, ?3)",
+                    params![key, model, serde_json::to_string(&value)?],
+                )?;
+            }
+            SqliteTable::KeyValues(namespace) => {
+                transaction.execute(
+                    "INSERT INTO key_values (namespace, key, data) VALUES (?1, ?2, ?3)",
+                    params![namespace, key, serde_json::to_string(&value)?],
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn remove(&self, transaction: &Transaction, key: &str) -> anyhow::Result<()> {
+        // messages are removed by the foreign key cascade
+        match &self.table {
+            SqliteTable::ChatRecords => {
+                transaction.execute("DELETE FROM chat_records WHERE key = ?1", [key])?
+            }
+            SqliteTable::Assistants => {
+                transaction.execute("DELETE FROM assistants WHERE name = ?1", [key])?
+            }
+            SqliteTable::KeyValues(namespace) => transaction.execute(
+                "DELETE FROM key_values WHERE namespace = ?1 AND key = ?2",
+                [namespace, key],
+            )?,
+        };
+
+        Ok(())
+    }
+
+    /// Values with their keys, chat records are ordered by creation date
+    fn select(&self, key: Option<&str>) -> anyhow::Result<Vec<(String, Value)>> {
+        let (query, args): (&str, Vec<&str>) = match (&self.table, key) {
+            (SqliteTable::ChatRecords, None) => (
+                "SELECT key, data FROM chat_records ORDER BY creation_date",
+                vec![],
+            ),
+            (SqliteTable::ChatRecords, Some(key)) => (
+                "SELECT key, data FROM chat_records WHERE key = ?1",
+                vec![key],
+            ),
+            (SqliteTable::Assistants, None) => {
+                ("SELECT name, data FROM assistants ORDER BY name", vec![])
+            }
+            (SqliteTable::Assistants, Some(key)) => (
+                "SELECT name, data FROM assistants WHERE name = ?1",
+                vec![key],
+            ),
+            (SqliteTable::KeyValues(namespace), None) => (
+                "SELECT key, data FROM key_values WHERE namespace = ?1 ORDER BY key",
+                vec![namespace.as_str()],
+            ),
+            (SqliteTable::KeyValues(namespace), Some(key)) => (
+                "SELECT key, data FROM key_values WHERE namespace = ?1 AND key = ?2",
+                vec![namespace.as_str(), key],
+            ),
+        };
+
+        let mut statement = self.connection.prepare(query)?;
+        let rows = statement
+            .query_map(rusqlite::params_from_iter(args), |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut entries = rows
+            .into_iter()
+            .map(|(key, data)| Ok((key, serde_json::from_str(&data)?)))
+            .collect::<anyhow::Result<Vec<(String, Value)>>>()?;
+
+        if let SqliteTable::ChatRecords = self.table {
+            let mut messages = self.select_messages(key)?;
+            for (key, record) in &mut entries {
+                if let Value::Object(record) = record {
+                    let messages = messages.remove(key.as_str()).unwrap_or_default();
+                    record.insert("messages".to_owned(), Value::Array(messages));
+                }
+            }
+        }
+
+        Ok(entries)
+    }
+
+    fn select_messages(&self, key: Option<&str>) -> anyhow::Result<HashMap<String, Vec<Value>>> {
+        let mut statement = self.connection.prepare(
+            "SELECT record_key, data FROM chat_messages WHERE ?1 IS NULL OR record_key = ?1 ORDER BY record_key, position",
+        )?;
+        let rows = statement
+            .query_map([key], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut messages: HashMap<String, Vec<Value>> = HashMap::new();
+        for (record_key, data) in rows {
+            messages
+                .entry(record_key)
+                .or_default()
+                .push(serde_json::from_str(&data)?);
+        }
+        Ok(messages)
+    }
+}
+
+impl<T> Storage<T> for SqliteStorage
+where
+    T: Serialize + for<'d> Deserialize<'d>,
+{
+    fn write(&self, item: &T) -> anyhow::Result<()> {
+        self.connection.execute(
+            "INSERT INTO documents (name, data) VALUES (?1, ?2) ON CONFLICT (name) DO UPDATE SET data = excluded.data",
+            params![self.table.name(), serde_json::to_string(item)?],
+        )?;
+        Ok(())
+    }
+
+    fn read(&self) -> anyhow::Result<Option<T>> {
+        let data: Option<String> = self
+            .connection
+            .query_row(
+                "SELECT data FROM documents WHERE name = ?1",
+                [self.table.name()],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        data.map(|data| serde_json::from_str(&data).map_err(anyhow::Error::from))
+            .transpose()
+    }
+}
+
+impl<V> KVStorage<String, V> for SqliteStorage
+where
+    V: Serialize + for<'d> Deserialize<'d>,
+{
+    fn add(&self, key: String, value: V) -> anyhow::Result<()> {
+        let transaction = self.connection.unchecked_transaction()?;
+        if self.contains(&transaction, &key)? {
+            bail!("key \"{key}\" already exists");
+        }
+
+        self.insert(&transaction, &key, serde_json::to_value(value)?)?;
+        transaction.commit().map_err(anyhow::Error::from)
+    }
+
+    fn get(&self, key: String) -> anyhow::Result<Option<V>> {
+        self.select(Some(&key))?
+            .into_iter()
+            .next()
+            .map(|(_, value)| serde_json::from_value(value).map_err(anyhow::Error::from))
+            .transpose()
+    }
+
+    fn get_all(&self) -> anyhow::Result<Vec<V>> {
+        self.select(None)?
+            .into_iter()
+            .map(|(_, value)| serde_json::from_value(value).map_err(anyhow::Error::from))
+            .collect()
+    }
+
+    fn get_all_entries(&self) -> anyhow::Result<Vec<(String, V)>> {
+        self.select(None)?
+            .into_iter()
+            .map(|(key, value)| Ok((key, serde_json::from_value(value)?)))
+            .collect()
+    }
+
+    fn update(&self, key: String, value: V) -> anyhow::Result<()> {
+        let transaction = self.connection.unchecked_transaction()?;
+        if !self.contains(&transaction, &key)? {
+            bail!("key \"{key}\" does not exist");
+        }
+
+        self.remove(&transaction, &key)?;
+        self.insert(&transaction, &key, serde_json::to_value(value)?)?;
+        transaction.commit().map_err(anyhow::Error::from)
+    }
+
+    fn delete(&self, key: String) -> anyhow::Result<()> {
+        let transaction = self.connection.unchecked_transaction()?;
+        if !self.contains(&transaction, &key)? {
+            bail!("key \"{key}\" does not exist");
+        }
+
+        self.remove(&transaction, &key)?;
+        transaction.commit().map_err(anyhow::Error::from)
+    }
+}
+
+fn migrate(connection: &Connection) -> anyhow::Result<()> {
+    let version: i64 = connection.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        let transaction = connection.unchecked_transaction()?;
+        transaction.execute_batch(migration)?;
+        transaction.pragma_update(None, "user_version", index as i64 + 1)?;
+        transaction.commit()?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_chat_records() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join(format!("explice-sqlite-test-{}", ulid::Ulid::new()));
+        let storage = SqliteStorage::open(dir.join("test.db"), SqliteTable::ChatRecords)?;
+        let record = json!({
+            "assistant_name": "assistant",
+            "creation_date": "2024-03-01T10:00:00+01:00",
+            "messages": [
+                {"role": "User", "content": "hi"},
+                {"role": "Assistant", "content": "hello"}
+            ]
+        });
+
+        storage.add("a".to_owned(), record.to_owned())?;
+        assert!(KVStorage::<String, Value>::add(&storage, "a".to_owned(), json!({})).is_err());
+        assert_eq!(storage.get("a".to_owned())?, Some(record.to_owned()));
+
+        let imported = storage.import(vec![
+            ("a".to_owned(), record.to_owned()),
+            ("b".to_owned(), record.to_owned()),
+        ])?;
+        assert_eq!(imported, 1);
+
+        KVStorage::<String, Value>::delete(&storage, "a".to_owned())?;
+        let records: Vec<Value> = storage.get_all()?;
+        assert_eq!(records, vec![record]);
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_chat_records_ordered_across_offsets() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join(format!("explice-sqlite-test-{}", ulid::Ulid::new()));
+        let storage = SqliteStorage::open(dir.join("test.db"), SqliteTable::ChatRecords)?;
+        let record = |creation_date: &str| json!({"assistant_name": "assistant", "creation_date": creation_date, "messages": []});
+
+        storage.add("later".to_owned(), record("2024-03-01T09:30:00.5Z"))?;
+        storage.add("earlier".to_owned(), record("2024-03-01T10:00:00+01:00"))?;
+        storage.add("unknown".to_owned(), record("yesterday"))?;
+
+        let entries: Vec<(String, Value)> = storage.get_all_entries()?;
+        let keys: Vec<_> = entries.iter().map(|(key, _)| key.as_str()).collect();
+        assert_eq!(keys, ["earlier", "later", "unknown"]);
+        assert_eq!(entries[0].1, record("2024-03-01T10:00:00+01:00"));
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}